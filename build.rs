@@ -1,7 +1,102 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use image::{
+    codecs::ico::{IcoEncoder, IcoFrame},
+    ExtendedColorType,
+};
+use resvg::{tiny_skia, usvg};
+
+/// Sizes in each rasterised ICO, covering tray icons at the usual scale factors
+const ICON_SIZES: [u32; 9] = [16, 20, 22, 24, 32, 36, 44, 48, 64];
+
 fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    rasterize_weather_icons();
+
+    // Listing any file disables the default rerun on every change, so list all used ones
+    println!("cargo:rerun-if-changed=assets/tabler-icons");
+    println!("cargo:rerun-if-changed=assets/resources.rc");
+    println!("cargo:rerun-if-changed=assets/app.ico");
+
     // Add ICO resources
     embed_resource::compile("assets/resources.rc", embed_resource::NONE)
         .manifest_optional()
         .unwrap();
     println!("cargo:rustc-link-arg-bin=tray-weather=/RES:assets/resources.res");
 }
+
+/// Rasterise `assets/weathericons/<theme>/svg/<name>.svg` into
+/// `$OUT_DIR/weathericons/<theme>/ico/<name>.ico` and list them in `$OUT_DIR/rasterized_icons.rs`
+fn rasterize_weather_icons() {
+    println!("cargo:rerun-if-changed=assets/weathericons");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let mut entries = String::new();
+    for theme_dir in sorted_entries(Path::new("assets/weathericons")) {
+        if !theme_dir.is_dir() {
+            continue;
+        }
+        let theme = theme_dir.file_name().unwrap().to_string_lossy();
+        let ico_dir = out_dir.join("weathericons").join(&*theme).join("ico");
+        fs::create_dir_all(&ico_dir).unwrap();
+        for svg in sorted_entries(&theme_dir.join("svg")) {
+            if svg.extension().is_none_or(|extension| extension != "svg") {
+                continue;
+            }
+            let name = svg.file_stem().unwrap().to_string_lossy();
+            let ico = ico_dir.join(format!("{name}.ico"));
+            fs::write(&ico, rasterize(&svg)).unwrap();
+            entries.push_str(&format!(
+                "    ({:?}, include_bytes!({:?})),\n",
+                format!("weathericons/{theme}/ico/{name}.ico"),
+                ico
+            ));
+        }
+    }
+    fs::write(
+        out_dir.join("rasterized_icons.rs"),
+        format!("&[\n{entries}]\n"),
+    )
+    .unwrap();
+}
+
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("{}: {err}", dir.display()))
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    entries.sort();
+    entries
+}
+
+/// Render an SVG file into an ICO with a PNG entry for each of [ICON_SIZES]
+fn rasterize(svg: &Path) -> Vec<u8> {
+    let tree = usvg::Tree::from_data(&fs::read(svg).unwrap(), &usvg::Options::default())
+        .unwrap_or_else(|err| panic!("{}: {err}", svg.display()));
+    let frames: Vec<_> = ICON_SIZES
+        .iter()
+        .map(|&size| {
+            let mut pixmap = tiny_skia::Pixmap::new(size, size).unwrap();
+            let scale = size as f32 / tree.size().width().max(tree.size().height());
+            resvg::render(
+                &tree,
+                tiny_skia::Transform::from_scale(scale, scale),
+                &mut pixmap.as_mut(),
+            );
+            let rgba: Vec<_> = pixmap
+                .pixels()
+                .iter()
+                .flat_map(|pixel| {
+                    let color = pixel.demultiply();
+                    [color.red(), color.green(), color.blue(), color.alpha()]
+                })
+                .collect();
+            IcoFrame::as_png(&rgba, size, size, ExtendedColorType::Rgba8).unwrap()
+        })
+        .collect();
+    let mut ico = vec![];
+    IcoEncoder::new(&mut ico).encode_images(&frames).unwrap();
+    ico
+}
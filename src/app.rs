@@ -1,47 +1,377 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
 use auto_launch::AutoLaunch;
-use log::{debug, trace};
-use tokio::sync::Notify;
-use tray_icon::menu::Menu;
+use chrono::Utc;
+use log::{debug, trace, warn};
+use reqwest::Client;
+use rust_i18n::t;
+use tokio::sync::{mpsc::Sender, watch, Notify};
+use tray_icon::menu::{CheckMenuItem, Menu, Submenu};
 
 use crate::{
-    error::Result, gui::weather_tray_icon::WeatherTrayIcon, settings::Settings,
-    weather::get_current_weather,
+    cache::CachedForecast,
+    error::{Error, Result},
+    gui::{
+        badge::Badge, color_scheme::ColorScheme, icon_theme::IconTheme,
+        weather_tray_icon::WeatherTrayIcon,
+    },
+    http::create_client_or_default,
+    provider::{create_provider, WeatherProvider},
+    retry::RetryPolicy,
+    settings::Settings,
+    units::UnitSettings,
+    weather::{Forecast, Location},
+    MenuId, Message,
 };
 
+/// Id of the tray icon of the active location
+const MAIN_TRAY_ID: &str = "main";
+
+fn pinned_tray_id(location: &Location) -> String {
+    format!("pinned_{}", location.id)
+}
+
+/// Result of fetching the forecast of a location in the background
+pub(crate) struct FetchedForecast {
+    /// [WeatherApp::generation] the fetch was started in
+    generation: u64,
+    location: Location,
+    result: Result<Forecast>,
+}
+
+/// Result of a fetch in progress, `None` until it finishes
+pub(crate) type PendingForecast = watch::Receiver<Option<std::result::Result<Forecast, String>>>;
+
+/// Tray icon showing the weather of one location
+pub(crate) struct LocationTray {
+    pub tray_icon: WeatherTrayIcon,
+    /// Last fetched forecast, shared with the forecast window
+    pub forecast: Arc<Mutex<Option<CachedForecast>>>,
+    /// Why the last fetch failed, until one succeeds again, shared with the forecast window
+    pub error: Arc<Mutex<Option<String>>>,
+}
+
+impl LocationTray {
+    fn new(tray_icon: WeatherTrayIcon, location: Option<&Location>) -> Self {
+        let tray = LocationTray {
+            tray_icon,
+            forecast: Arc::new(Mutex::new(None)),
+            error: Arc::new(Mutex::new(None)),
+        };
+        tray.reload_forecast(location);
+        tray
+    }
+
+    /// Replace the shared forecast by the cached one of `location`
+    fn reload_forecast(&self, location: Option<&Location>) {
+        *self.forecast.lock().unwrap() =
+            location.and_then(|location| CachedForecast::load(location).ok());
+        *self.error.lock().unwrap() = None;
+    }
+
+    /// Store the result of fetching the forecast of `location` and show it
+    fn show_result(
+        &self,
+        location: &Location,
+        result: Result<Forecast>,
+        icon_theme: &IconTheme,
+        units: &UnitSettings,
+    ) -> Result<()> {
+        trace!("{:?}", result);
+
+        *self.error.lock().unwrap() = match result {
+            Ok(new_forecast) => {
+                let cached = CachedForecast::new(new_forecast);
+                if let Err(err) = cached.save(location) {
+                    warn!("Could not cache forecast: {}", err);
+                }
+                *self.forecast.lock().unwrap() = Some(cached);
+                None
+            }
+            Err(err) => Some(err.to_string()),
+        };
+        self.show(location, icon_theme, units)
+    }
+
+    /// Show the shared forecast, marked as stale if the last fetch failed
+    fn show(
+        &self,
+        location: &Location,
+        icon_theme: &IconTheme,
+        units: &UnitSettings,
+    ) -> Result<()> {
+        let forecast = self.forecast.lock().unwrap();
+        let error = self.error.lock().unwrap().clone();
+
+        // After a failed fetch, keep showing the last known weather, preferring the current hour
+        let now = Utc::now();
+        let weather = forecast.as_ref().and_then(|cached| {
+            let current = error.is_none().then(|| cached.forecast.current.clone());
+            let weather = current
+                .flatten()
+                .or_else(|| cached.observation(now))
+                .or_else(|| cached.forecast.current.clone())?;
+            let badges = Badge::for_forecast(&cached.forecast, now);
+            Some((weather, badges, cached.fetched))
+        });
+        match (weather, error) {
+            (Some((weather, badges, _)), None) => self
+                .tray_icon
+                .set_weather(location, icon_theme, units, &weather, &badges),
+            (Some((weather, badges, fetched)), Some(_)) => self
+                .tray_icon
+                .set_stale_weather(location, icon_theme, units, &weather, &badges, &fetched),
+            (None, error) => {
+                let error = error
+                    .unwrap_or_else(|| Error::other("No current weather received.").to_string());
+                self.tray_icon.set_error(&format!("Fehler: {}", error))
+            }
+        }
+    }
+}
+
 pub(crate) struct WeatherApp {
     pub settings: Settings,
-    pub tray_icon: WeatherTrayIcon,
+    /// Tray icon of the active location
+    pub tray: LocationTray,
+    /// Additional tray icons of pinned locations
+    pub pinned: Vec<(Location, LocationTray)>,
+    pub client: Client,
+    pub provider: Arc<dyn WeatherProvider>,
+    /// Tray submenu to switch between saved locations
+    pub locations_menu: Submenu,
+    /// Color scheme reported by the system, if it has one
+    pub system_color_scheme: Option<ColorScheme>,
+    /// Receives [Message::Fetched] of background fetches
+    tx: Sender<Message>,
+    /// Fetches in progress by location id
+    fetching: HashMap<u32, PendingForecast>,
+    /// Counts provider changes, to drop results fetched by an old provider
+    generation: u64,
 }
 
 impl WeatherApp {
-    pub fn new(settings: Settings, menu: Menu) -> Result<Self> {
-        let tray_icon = WeatherTrayIcon::new(menu)?;
-        Ok(WeatherApp {
+    pub fn new(
+        settings: Settings,
+        menu: Menu,
+        locations_menu: Submenu,
+        tx: Sender<Message>,
+    ) -> Result<Self> {
+        let tray_icon = WeatherTrayIcon::new(MAIN_TRAY_ID, Some(menu))?;
+        let tray = LocationTray::new(tray_icon, settings.location());
+        let client = create_client_or_default(&settings.http);
+        let provider = create_provider(&settings, client.clone());
+        let mut app = WeatherApp {
             settings,
-            tray_icon,
-        })
+            tray,
+            pinned: vec![],
+            client,
+            provider,
+            locations_menu,
+            system_color_scheme: ColorScheme::detect(),
+            tx,
+            fetching: HashMap::new(),
+            generation: 0,
+        };
+        app.update_locations_menu()?;
+        app.update_pinned()?;
+        Ok(app)
     }
 
-    pub async fn update_weather(&self) -> Result<()> {
+    /// Fetch the weather of all shown locations in the background
+    pub fn update_weather(&mut self) -> Result<()> {
         debug!("update_weather()");
-        let weather = get_current_weather(&self.settings.location).await;
-        trace!("{:?}", weather);
-        match weather {
-            Ok(weather) => self.tray_icon.set_weather(
-                &self.settings.location,
-                &self.settings.icon_theme,
-                &weather,
-            )?,
-            Err(err) => self.tray_icon.set_error(&format!("Fehler: {}", err))?,
+        self.update_color_scheme();
+        match self.settings.location().cloned() {
+            Some(location) => {
+                self.fetch(location, RetryPolicy::default());
+            }
+            None => self.tray.tray_icon.set_error(&t!("empty_location"))?,
+        }
+        let pinned: Vec<_> = self
+            .pinned
+            .iter()
+            .map(|(location, _)| location.clone())
+            .collect();
+        for location in pinned {
+            self.fetch(location, RetryPolicy::default());
+        }
+        Ok(())
+    }
+
+    /// Fetch the forecast of `location` in a task, which sends it back as [Message::Fetched]
+    fn fetch(&mut self, location: Location, retry: RetryPolicy) -> PendingForecast {
+        if let Some(pending) = self.fetching.get(&location.id) {
+            debug!("Already fetching weather of {}", location.name);
+            return pending.clone();
+        }
+        let (done, pending) = watch::channel(None);
+        self.fetching.insert(location.id, pending.clone());
+        let generation = self.generation;
+        let provider = self.provider.clone();
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let result = retry.run(|| provider.forecast(&location)).await;
+            done.send_replace(Some(result.as_ref().cloned().map_err(ToString::to_string)));
+            let fetched = FetchedForecast {
+                generation,
+                location,
+                result,
+            };
+            let _ = tx.send(Message::Fetched(Box::new(fetched))).await;
+        });
+        pending
+    }
+
+    /// Fetch the weather of the tray with `id` without retrying, as the user waits for it
+    pub fn fetch_tray(&mut self, id: &str) -> Option<PendingForecast> {
+        let location = self
+            .pinned
+            .iter()
+            .find(|(location, _)| pinned_tray_id(location) == id)
+            .map(|(location, _)| location)
+            .or(self.settings.location())
+            .cloned()?;
+        Some(self.fetch(location, RetryPolicy::once()))
+    }
+
+    /// Show a forecast fetched in the background on the tray of its location
+    pub fn show_fetched(&mut self, fetched: FetchedForecast) -> Result<()> {
+        if fetched.generation != self.generation {
+            return Ok(());
+        }
+        self.fetching.remove(&fetched.location.id);
+        let is_active = |location: &Location| location.id == fetched.location.id;
+        let shown = match self
+            .settings
+            .location()
+            .filter(|location| is_active(location))
+        {
+            Some(location) => Some((location, &self.tray)),
+            None => self
+                .pinned
+                .iter()
+                .find(|(location, _)| is_active(location))
+                .map(|(location, tray)| (location, tray)),
         };
+        // The location may have been removed while it was fetched
+        let Some((location, tray)) = shown else {
+            return Ok(());
+        };
+        tray.show_result(
+            location,
+            fetched.result,
+            &self.settings.icon_theme,
+            &self.settings.units,
+        )?;
+
+        if self.provider.save_state(&mut self.settings) {
+            self.settings.save()?;
+        }
         Ok(())
     }
 
-    pub async fn update_settings(&mut self) -> Result<()> {
+    /// Tray icon with the id sent in its events
+    pub fn tray_by_id(&self, id: &str) -> &LocationTray {
+        self.pinned
+            .iter()
+            .find(|(location, _)| pinned_tray_id(location) == id)
+            .map_or(&self.tray, |(_, tray)| tray)
+    }
+
+    pub fn update_settings(&mut self) -> Result<()> {
+        self.client = create_client_or_default(&self.settings.http);
+        self.provider = create_provider(&self.settings, self.client.clone());
+        self.generation += 1;
+        self.fetching.clear();
+        self.tray.reload_forecast(self.settings.location());
+        self.update_locations_menu()?;
+        self.update_pinned()?;
         self.set_autorun(self.settings.autorun_enabled)?;
-        self.update_weather().await?;
+        self.update_weather()
+    }
+
+    /// Redraw the weather if the system color scheme changed
+    pub fn set_system_color_scheme(&mut self, color_scheme: Option<ColorScheme>) -> Result<()> {
+        if color_scheme == self.system_color_scheme {
+            return Ok(());
+        }
+        self.system_color_scheme = color_scheme;
+        self.update_color_scheme();
+        if let Some(location) = self.settings.location() {
+            self.tray
+                .show(location, &self.settings.icon_theme, &self.settings.units)?;
+        }
+        for (location, tray) in &self.pinned {
+            tray.show(location, &self.settings.icon_theme, &self.settings.units)?;
+        }
+        Ok(())
+    }
+
+    /// Draw the next icons of all trays for the selected color scheme
+    fn update_color_scheme(&self) {
+        let color_scheme = self.settings.color_scheme.resolve(self.system_color_scheme);
+        self.tray.tray_icon.set_color_scheme(color_scheme);
+        for (_, tray) in &self.pinned {
+            tray.tray_icon.set_color_scheme(color_scheme);
+        }
+    }
+
+    /// Switch to another saved location
+    pub fn select_location(&mut self, index: usize) -> Result<()> {
+        if index >= self.settings.locations.len() {
+            return Ok(());
+        }
+        self.settings.active_location = index;
+        self.settings.save()?;
+        self.tray.reload_forecast(self.settings.location());
+        self.update_locations_menu()?;
+        self.update_pinned()?;
+        if let Some(location) = self.settings.location().cloned() {
+            self.fetch(location, RetryPolicy::once());
+        }
+        Ok(())
+    }
+
+    /// Create tray icons for pinned locations, except the active one
+    fn update_pinned(&mut self) -> Result<()> {
+        let mut pinned = vec![];
+        for location in self.settings.pinned_locations() {
+            let tray = match self
+                .pinned
+                .iter()
+                .position(|(old, _)| old.id == location.id)
+            {
+                Some(index) => self.pinned.swap_remove(index).1,
+                None => {
+                    let tray_icon = WeatherTrayIcon::new(&pinned_tray_id(location), None)?;
+                    LocationTray::new(tray_icon, Some(location))
+                }
+            };
+            pinned.push((location.clone(), tray));
+        }
+        self.pinned = pinned;
+        Ok(())
+    }
+
+    /// Rebuild the locations submenu, checking the active location
+    fn update_locations_menu(&self) -> Result<()> {
+        while self.locations_menu.remove_at(0).is_some() {}
+        for (index, location) in self.settings.locations.iter().enumerate() {
+            let item = CheckMenuItem::with_id(
+                MenuId::Location(index),
+                &location.name,
+                true,
+                index == self.settings.active_location,
+                None,
+            );
+            self.locations_menu.append(&item)?;
+        }
+        self.locations_menu
+            .set_enabled(!self.settings.locations.is_empty());
         Ok(())
     }
 
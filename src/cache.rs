@@ -0,0 +1,96 @@
+use std::{fs, path::PathBuf};
+
+use chrono::{DateTime, Duration, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    weather::{Forecast, Location, Observation},
+};
+
+/// Last successfully fetched [Forecast] of a [Location], kept on disk
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct CachedForecast {
+    pub fetched: DateTime<Utc>,
+    pub forecast: Forecast,
+}
+
+impl CachedForecast {
+    pub fn new(forecast: Forecast) -> Self {
+        Self {
+            fetched: Utc::now(),
+            forecast,
+        }
+    }
+
+    fn get_path(location: &Location) -> Result<PathBuf> {
+        let proj_dirs = ProjectDirs::from("de", "osor", "TrayWeather")
+            .ok_or(Error::other("Failed to get cache directory."))?;
+        Ok(proj_dirs
+            .cache_dir()
+            .join(format!("forecast_{}.json", location.id)))
+    }
+
+    pub fn load(location: &Location) -> Result<Self> {
+        let cache_string = fs::read_to_string(Self::get_path(location)?)?;
+        serde_json::from_str(&cache_string).map_err(Error::other)
+    }
+
+    pub fn save(&self, location: &Location) -> Result<()> {
+        let path = Self::get_path(location)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let cache_string = serde_json::to_string(&self).map_err(Error::other)?;
+        fs::write(path, cache_string)?;
+        Ok(())
+    }
+
+    /// Weather expected at `now`, taken from the hourly forecast
+    pub fn observation(&self, now: DateTime<Utc>) -> Option<Observation> {
+        let now = self.forecast.local_now(now);
+        self.forecast
+            .hourly
+            .iter()
+            .find(|point| point.time <= now && now < point.time + Duration::hours(1))
+            .map(Observation::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration};
+
+    use crate::{
+        provider::open_meteo::WeatherResponse,
+        weather::{Forecast, WeatherCondition},
+    };
+
+    use super::CachedForecast;
+
+    #[test]
+    fn cached_observation() {
+        let response: WeatherResponse =
+            serde_json::from_str(include_str!("../tests/data/weather_response_forecast.json"))
+                .unwrap();
+        let cached = CachedForecast::new(Forecast::from(response));
+
+        let json = serde_json::to_string(&cached).unwrap();
+        let cached: CachedForecast = serde_json::from_str(&json).unwrap();
+
+        let time = DateTime::parse_from_rfc3339("2024-10-21T17:20:00Z")
+            .unwrap()
+            .to_utc();
+        let observation = cached.observation(time).unwrap();
+        assert_eq!(observation.condition, WeatherCondition::Overcast);
+        assert_eq!(observation.temperature.value, 17.0);
+        assert!(cached.observation(time + Duration::hours(1)).is_none());
+
+        // Times of the forecast are local to the location
+        let mut cached = cached;
+        cached.forecast.utc_offset_seconds = 7200;
+        assert!(cached.observation(time).is_none());
+        assert!(cached.observation(time - Duration::hours(2)).is_some());
+    }
+}
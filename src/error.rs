@@ -1,6 +1,6 @@
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 
-use crate::weather::WeatherError;
+use crate::provider::open_meteo::WeatherError;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -10,6 +10,7 @@ pub enum Error {
     Io(std::io::Error),
     NoSettings,
     Other(Box<dyn std::error::Error>),
+    RateLimited(Option<Duration>),
     Reqwest(reqwest::Error),
     TomlDe(toml::de::Error),
     TomlSer(toml::ser::Error),
@@ -32,6 +33,7 @@ impl Display for Error {
             Io(io_error) => write!(f, "{io_error}"),
             NoSettings => write!(f, "No Settings were provided."),
             Other(err) => write!(f, "Other error: {}", err),
+            RateLimited(_) => write!(f, "Too many requests."),
             Reqwest(err) => write!(f, "RequestError: {}", err),
             TomlDe(err) => write!(f, "TomlDeError: {}", err),
             TomlSer(err) => write!(f, "TomlSerError: {}", err),
@@ -49,6 +51,27 @@ impl Error {
     {
         Self::Other(error.into())
     }
+
+    /// Whether the failed request may succeed when sent again
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::RateLimited(_) => true,
+            Error::Reqwest(err) => {
+                err.is_timeout()
+                    || err.is_connect()
+                    || err.status().is_some_and(|status| status.is_server_error())
+            }
+            _ => false,
+        }
+    }
+
+    /// Time the server asked to wait before sending the request again
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Error::RateLimited(retry_after) => *retry_after,
+            _ => None,
+        }
+    }
 }
 
 impl From<auto_launch::Error> for Error {
@@ -0,0 +1,88 @@
+use chrono::{DateTime, Utc};
+use image::{imageops, RgbaImage};
+
+use crate::{
+    error::Result,
+    weather::{get_icon_image, Forecast},
+};
+
+/// Small overlay in a corner of the tray icon
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Badge {
+    /// A weather alert is active
+    Alert,
+    /// The weather shown could not be refreshed
+    Stale,
+    /// Precipitation is expected within the hour
+    Precipitation,
+}
+
+impl Badge {
+    /// Badges for the state of `forecast` at `now`
+    pub fn for_forecast(forecast: &Forecast, now: DateTime<Utc>) -> Vec<Badge> {
+        let mut badges = vec![];
+        if forecast.has_active_alert(now) {
+            badges.push(Badge::Alert);
+        }
+        if forecast.precipitation_expected(now) {
+            badges.push(Badge::Precipitation);
+        }
+        badges
+    }
+
+    fn path(&self) -> &'static str {
+        match self {
+            Badge::Alert => "tabler-icons/alert-triangle.svg",
+            Badge::Stale => "tabler-icons/clock.svg",
+            Badge::Precipitation => "tabler-icons/droplet.svg",
+        }
+    }
+
+    /// Position of the badge's top left corner on an icon of `size` pixels
+    fn position(&self, size: u32) -> (i64, i64) {
+        let far = (size - badge_size(size)) as i64;
+        match self {
+            Badge::Alert => (0, 0),
+            Badge::Stale => (far, far),
+            Badge::Precipitation => (0, far),
+        }
+    }
+}
+
+fn badge_size(size: u32) -> u32 {
+    size.div_ceil(2)
+}
+
+/// Composite `badges` onto the corners of `image`
+pub fn add_badges(image: &mut RgbaImage, badges: &[Badge]) -> Result<()> {
+    let size = image.width().min(image.height());
+    for badge in badges {
+        let badge_image = get_icon_image(badge.path(), badge_size(size))?;
+        let (x, y) = badge.position(size);
+        imageops::overlay(image, &badge_image, x, y);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::{add_badges, Badge};
+
+    #[test]
+    fn add_corner_badges() {
+        let transparent = Rgba([0, 0, 0, 0]);
+        let mut image = RgbaImage::from_pixel(22, 22, transparent);
+        add_badges(&mut image, &[Badge::Alert, Badge::Stale]).unwrap();
+        let quarter_visible = |x0: u32, y0: u32| {
+            (x0..x0 + 11)
+                .flat_map(|x| (y0..y0 + 11).map(move |y| (x, y)))
+                .any(|(x, y)| *image.get_pixel(x, y) != transparent)
+        };
+        assert!(quarter_visible(0, 0));
+        assert!(quarter_visible(11, 11));
+        assert!(!quarter_visible(0, 11));
+        assert!(!quarter_visible(11, 0));
+    }
+}
@@ -0,0 +1,237 @@
+use std::{fmt::Display, slice::Iter};
+
+use log::debug;
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+
+/// How often the color scheme is checked where the system does not report changes
+#[cfg(not(target_os = "linux"))]
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Color scheme of the taskbar tray icons are shown on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ColorScheme {
+    Light,
+    Dark,
+}
+
+/// Color scheme selected in settings
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorSchemeSetting {
+    /// Follow the color scheme of the system
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Display for ColorSchemeSetting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorSchemeSetting::System => f.write_str(&t!("color_scheme_system")),
+            ColorSchemeSetting::Light => f.write_str(&t!("color_scheme_light")),
+            ColorSchemeSetting::Dark => f.write_str(&t!("color_scheme_dark")),
+        }
+    }
+}
+
+impl ColorSchemeSetting {
+    pub fn iterator() -> Iter<'static, ColorSchemeSetting> {
+        use ColorSchemeSetting::*;
+        static COLOR_SCHEMES: [ColorSchemeSetting; 3] = [System, Light, Dark];
+        COLOR_SCHEMES.iter()
+    }
+
+    /// The selected color scheme, or `system` if none is selected
+    pub(crate) fn resolve(&self, system: Option<ColorScheme>) -> Option<ColorScheme> {
+        match self {
+            ColorSchemeSetting::System => system,
+            ColorSchemeSetting::Light => Some(ColorScheme::Light),
+            ColorSchemeSetting::Dark => Some(ColorScheme::Dark),
+        }
+    }
+}
+
+impl ColorScheme {
+    /// Color scheme of the system, if it has a preference
+    pub fn detect() -> Option<ColorScheme> {
+        #[cfg(target_os = "linux")]
+        let scheme = portal::read().unwrap_or_else(|err| {
+            debug!("Could not read color scheme from desktop portal: {err}");
+            None
+        });
+        #[cfg(windows)]
+        let scheme = windows::read();
+        #[cfg(target_os = "macos")]
+        let scheme = macos::read();
+        #[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+        let scheme = None;
+        scheme
+    }
+
+    /// Call `callback` with the system color scheme whenever it changes
+    pub fn watch<F>(callback: F)
+    where
+        F: Fn(Option<ColorScheme>) + Send + 'static,
+    {
+        std::thread::spawn(move || {
+            #[cfg(target_os = "linux")]
+            if let Err(err) = portal::watch(callback) {
+                log::warn!("Could not watch color scheme of desktop portal: {err}");
+            }
+            #[cfg(not(target_os = "linux"))]
+            {
+                let mut scheme = ColorScheme::detect();
+                loop {
+                    std::thread::sleep(POLL_INTERVAL);
+                    let new_scheme = ColorScheme::detect();
+                    if new_scheme != scheme {
+                        debug!("Color scheme changed to {new_scheme:?}");
+                        callback(new_scheme);
+                        scheme = new_scheme;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// `org.freedesktop.appearance.color-scheme` of the XDG desktop portal
+#[cfg(target_os = "linux")]
+mod portal {
+    use log::debug;
+    use zbus::{
+        blocking::{Connection, Proxy},
+        zvariant::{OwnedValue, Value},
+    };
+
+    use super::ColorScheme;
+
+    const DESTINATION: &str = "org.freedesktop.portal.Desktop";
+    const PATH: &str = "/org/freedesktop/portal/desktop";
+    const SETTINGS_INTERFACE: &str = "org.freedesktop.portal.Settings";
+    const NAMESPACE: &str = "org.freedesktop.appearance";
+    const KEY: &str = "color-scheme";
+
+    fn settings_proxy(connection: &Connection) -> zbus::Result<Proxy<'_>> {
+        Proxy::new(connection, DESTINATION, PATH, SETTINGS_INTERFACE)
+    }
+
+    pub fn read() -> zbus::Result<Option<ColorScheme>> {
+        let connection = Connection::session()?;
+        let value: OwnedValue = settings_proxy(&connection)?.call("Read", &(NAMESPACE, KEY))?;
+        Ok(from_value(&value))
+    }
+
+    pub fn watch<F: Fn(Option<ColorScheme>)>(callback: F) -> zbus::Result<()> {
+        let connection = Connection::session()?;
+        let proxy = settings_proxy(&connection)?;
+        for message in proxy.receive_signal("SettingChanged")? {
+            let Ok((namespace, key, value)) = message.body::<(String, String, OwnedValue)>() else {
+                continue;
+            };
+            if namespace == NAMESPACE && key == KEY {
+                let scheme = from_value(&value);
+                debug!("Color scheme changed to {scheme:?}");
+                callback(scheme);
+            }
+        }
+        Ok(())
+    }
+
+    /// Map `1` (prefer dark) and `2` (prefer light), `Read` wraps it into another variant
+    pub(super) fn from_value(value: &Value) -> Option<ColorScheme> {
+        match value {
+            Value::Value(value) => from_value(value),
+            Value::U32(1) => Some(ColorScheme::Dark),
+            Value::U32(2) => Some(ColorScheme::Light),
+            _ => None,
+        }
+    }
+}
+
+/// `SystemUsesLightTheme` of the Windows registry, which the taskbar follows
+#[cfg(windows)]
+mod windows {
+    use windows_sys::{
+        core::w,
+        Win32::{
+            Foundation::ERROR_SUCCESS,
+            System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD},
+        },
+    };
+
+    use super::ColorScheme;
+
+    pub fn read() -> Option<ColorScheme> {
+        let mut value: u32 = 0;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        let result = unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                w!("Software\\Microsoft\\Windows\\CurrentVersion\\Themes\\Personalize"),
+                w!("SystemUsesLightTheme"),
+                RRF_RT_REG_DWORD,
+                std::ptr::null_mut(),
+                &mut value as *mut u32 as *mut _,
+                &mut size,
+            )
+        };
+        (result == ERROR_SUCCESS).then_some(match value {
+            0 => ColorScheme::Dark,
+            _ => ColorScheme::Light,
+        })
+    }
+}
+
+/// `AppleInterfaceStyle` of the global defaults, which is only set in dark mode
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::process::Command;
+
+    use super::ColorScheme;
+
+    pub fn read() -> Option<ColorScheme> {
+        let output = Command::new("defaults")
+            .args(["read", "-g", "AppleInterfaceStyle"])
+            .output()
+            .ok()?;
+        match String::from_utf8_lossy(&output.stdout).trim() {
+            "Dark" => Some(ColorScheme::Dark),
+            _ => Some(ColorScheme::Light),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorScheme, ColorSchemeSetting};
+
+    #[test]
+    fn resolve_setting() {
+        assert_eq!(
+            ColorSchemeSetting::System.resolve(Some(ColorScheme::Light)),
+            Some(ColorScheme::Light)
+        );
+        assert_eq!(ColorSchemeSetting::System.resolve(None), None);
+        assert_eq!(
+            ColorSchemeSetting::Dark.resolve(Some(ColorScheme::Light)),
+            Some(ColorScheme::Dark)
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn read_portal_values() {
+        use zbus::zvariant::Value;
+
+        use super::portal::from_value;
+
+        assert_eq!(from_value(&Value::U32(1)), Some(ColorScheme::Dark));
+        assert_eq!(
+            from_value(&Value::Value(Box::new(Value::U32(2)))),
+            Some(ColorScheme::Light)
+        );
+        assert_eq!(from_value(&Value::U32(0)), None);
+    }
+}
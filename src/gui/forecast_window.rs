@@ -1,64 +1,237 @@
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Arc, Mutex,
+};
 
-use chrono::{DateTime, Duration, Local, TimeZone, Timelike};
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, Timelike, Utc};
 use eframe::egui::{self, Color32, Layout, Margin, RichText, TextBuffer, Ui};
-use log::trace;
+use log::warn;
 use rust_i18n::t;
 
 use crate::{
+    app::PendingForecast,
+    cache::CachedForecast,
     error::{Error, Result},
+    provider::WeatherProvider,
+    retry::RetryPolicy,
     settings::Settings,
-    weather::{get_forecast, WeatherResponse},
+    units::{Precipitation, UnitSettings},
+    weather::{DailyPoint, Forecast, HourlyPoint, Observation, WeatherCondition},
     PROGRAM_NAME,
 };
 
+use super::ForecastTime;
+
+#[derive(Clone, Copy, PartialEq)]
+enum ForecastView {
+    Forecast,
+    Compare,
+}
+
+/// Forecast of one location in the comparison view
+struct ComparisonColumn {
+    /// Fetched forecast, or the cached one until then
+    forecast: Option<Forecast>,
+    /// Why the forecast could not be fetched
+    error: Option<String>,
+}
+
 pub(crate) struct ForecastWindow {
-    pub loading: bool,
+    /// Forecast shared with the tray icon, which is only updated after the window is closed
+    pub forecast: Arc<Mutex<Option<CachedForecast>>>,
+    /// Why the last fetch of the tray icon failed
+    error: Arc<Mutex<Option<String>>>,
+    /// Fetch started for the window if there was no forecast yet
+    pending: Option<PendingForecast>,
     pub settings: Settings,
-    pub rx: Receiver<Result<WeatherResponse>>,
-    pub tx: Sender<Result<WeatherResponse>>,
-    pub weather_response: Option<WeatherResponse>,
+    pub provider: Arc<dyn WeatherProvider>,
+    view: ForecastView,
+    /// Columns of all saved locations for the comparison view
+    comparison: Option<Vec<ComparisonColumn>>,
+    rx: Receiver<(usize, Result<Forecast>)>,
+    tx: Sender<(usize, Result<Forecast>)>,
 }
 
 impl ForecastWindow {
-    fn new(settings: Settings) -> Self {
+    fn new(
+        forecast: Arc<Mutex<Option<CachedForecast>>>,
+        error: Arc<Mutex<Option<String>>>,
+        pending: Option<PendingForecast>,
+        settings: Settings,
+        provider: Arc<dyn WeatherProvider>,
+    ) -> Self {
+        let (tx, rx) = channel();
         Self {
+            forecast,
+            error,
+            pending,
             settings,
-            ..Default::default()
+            provider,
+            view: ForecastView::Forecast,
+            comparison: None,
+            rx,
+            tx,
+        }
+    }
+
+    /// Fetch forecasts of all locations concurrently, showing cached ones meanwhile
+    ///
+    /// The results are only shown here, the cache of each location is left to its tray icon.
+    fn update_comparison(&mut self, ctx: &egui::Context) {
+        let comparison = self
+            .settings
+            .locations
+            .iter()
+            .map(|location| ComparisonColumn {
+                forecast: CachedForecast::load(location).ok().map(|c| c.forecast),
+                error: None,
+            })
+            .collect();
+        self.comparison = Some(comparison);
+
+        for (index, location) in self.settings.locations.iter().cloned().enumerate() {
+            let tx = self.tx.clone();
+            let provider = self.provider.clone();
+            let ctx = ctx.clone();
+            tokio::spawn(async move {
+                let forecast = RetryPolicy::once()
+                    .run(|| provider.forecast(&location))
+                    .await;
+                let _ = tx.send((index, forecast));
+                ctx.request_repaint();
+            });
+        }
+    }
+
+    /// Render the shared forecast, or the one fetched for the window until there is one
+    fn render_forecast(&self, ui: &mut Ui) {
+        let cached = self.forecast.lock().unwrap();
+        if let Some(CachedForecast { ref forecast, .. }) = *cached {
+            return self.render_forecast_of(ui, forecast);
+        }
+        let fetched = self.pending.as_ref().map(|pending| pending.borrow());
+        match fetched.as_deref() {
+            Some(Some(Ok(forecast))) => self.render_forecast_of(ui, forecast),
+            Some(Some(Err(error))) => {
+                ui.colored_label(
+                    ui.visuals().error_fg_color,
+                    t!("forecast_error", error = error),
+                );
+            }
+            _ => {
+                // Show the last error while fetching again, or alone if nothing is fetched
+                if let Some(ref error) = *self.error.lock().unwrap() {
+                    ui.colored_label(
+                        ui.visuals().error_fg_color,
+                        t!("forecast_error", error = error),
+                    );
+                    if fetched.is_none() {
+                        return;
+                    }
+                }
+                ui.with_layout(
+                    Layout::centered_and_justified(egui::Direction::LeftToRight),
+                    |ui| {
+                        ui.label(t!("loading"));
+                    },
+                );
+                // The fetch does not know about the window, so look for its result
+                ui.ctx()
+                    .request_repaint_after(std::time::Duration::from_millis(250));
+            }
         }
     }
 
-    fn update_weather(&mut self) {
-        trace!("update_weather()");
-        self.loading = true;
-        let tx = self.tx.clone();
-        let location = self.settings.location.clone();
-        tokio::spawn(async move {
-            let forecast = get_forecast(&location).await;
-            tx.send(forecast).unwrap();
+    fn render_forecast_of(&self, ui: &mut Ui, forecast: &Forecast) {
+        let units = &self.settings.units;
+        let offset = display_offset(&self.settings.forecast_time, forecast);
+        ui.vertical(|ui| {
+            // current weather
+            if let Some(ref cur) = forecast.current {
+                current_card(ui, cur, units);
+            }
+
+            // hourly forecast
+            ui.horizontal_top(|ui| {
+                for point in &forecast.hourly {
+                    let time = forecast.local_time(point.time).with_timezone(&offset);
+                    hour_card(ui, &time, point, units);
+                }
+            });
+
+            // daily forecast
+            ui.horizontal_top(|ui| {
+                for point in &forecast.daily {
+                    day_card(ui, point, units, &offset);
+                }
+            });
         });
     }
-}
 
-impl Default for ForecastWindow {
-    fn default() -> Self {
-        let (tx, rx) = channel();
-        Self {
-            loading: false,
-            settings: Default::default(),
-            rx,
-            tx,
-            weather_response: None,
+    fn render_comparison(&mut self, ui: &mut Ui) {
+        let units = &self.settings.units;
+        let Some(comparison) = self.comparison.as_mut() else {
+            return;
+        };
+        while let Ok((index, response)) = self.rx.try_recv() {
+            let Some(column) = comparison.get_mut(index) else {
+                continue;
+            };
+            match response {
+                Ok(forecast) => {
+                    column.forecast = Some(forecast);
+                    column.error = None;
+                }
+                Err(err) => {
+                    warn!("Could not get forecast: {}", err);
+                    column.error = Some(err.to_string());
+                }
+            }
         }
+
+        egui::ScrollArea::both().show(ui, |ui| {
+            ui.horizontal_top(|ui| {
+                for (location, column) in self.settings.locations.iter().zip(comparison.iter()) {
+                    ui.vertical(|ui| {
+                        ui.heading(&location.name);
+                        if let Some(ref error) = column.error {
+                            ui.colored_label(
+                                ui.visuals().error_fg_color,
+                                t!("forecast_error", error = error),
+                            );
+                        }
+                        let Some(ref forecast) = column.forecast else {
+                            if column.error.is_none() {
+                                ui.label(t!("loading"));
+                            }
+                            return;
+                        };
+                        if let Some(ref cur) = forecast.current {
+                            current_card(ui, cur, units);
+                        }
+                        let offset = display_offset(&self.settings.forecast_time, forecast);
+                        for point in &forecast.daily {
+                            day_card(ui, point, units, &offset);
+                        }
+                    });
+                }
+            });
+        });
     }
 }
 
-fn render_current(ui: &mut Ui, _weathercode: u16, temperature: f32, rain: f32, wind_speed: f32) {
+fn render_current(
+    ui: &mut Ui,
+    _condition: WeatherCondition,
+    temperature: &str,
+    rain: &str,
+    wind_speed: &str,
+) {
     egui::Frame::none()
         .stroke(egui::Stroke::new(1.0, Color32::from_rgb(240, 240, 240)))
         .show(ui, |ui| {
             ui.horizontal(|ui| {
-                ui.label(RichText::new(temperature.to_string()).size(30.0));
+                ui.label(RichText::new(temperature).size(30.0));
                 ui.vertical(|ui| {
                     ui.label(format!("{}: {}", t!("wind"), wind_speed));
                     ui.label(format!("{}: {}", t!("rain"), rain));
@@ -140,10 +313,10 @@ fn render_day(
 fn render_hour(
     ui: &mut Ui,
     hour: &str,
-    _weather_code: u16,
-    temperature: f32,
-    precipitation: f32,
-    wind_speed_10m: f32,
+    _condition: WeatherCondition,
+    temperature: &str,
+    precipitation: &str,
+    wind_speed_10m: &str,
 ) {
     egui::Frame::none()
         .stroke(egui::Stroke::new(1.0, Color32::from_rgb(240, 240, 240)))
@@ -161,7 +334,7 @@ fn render_hour(
                 egui::Frame::none()
                     .inner_margin(Margin::symmetric(10.0, 10.0))
                     .show(ui, |ui| {
-                        ui.label(RichText::new(temperature.to_string()).size(20.0));
+                        ui.label(RichText::new(temperature).size(20.0));
                     });
 
                 // wind dir
@@ -169,7 +342,7 @@ fn render_hour(
                 egui::Frame::none()
                     .inner_margin(Margin::symmetric(10.0, 10.0))
                     .show(ui, |ui| {
-                        ui.label(RichText::new(wind_speed_10m.to_string()).heading());
+                        ui.label(RichText::new(wind_speed_10m).heading());
                     });
 
                 // ui.image(weathericon);
@@ -177,14 +350,60 @@ fn render_hour(
                 egui::Frame::none()
                     .inner_margin(Margin::symmetric(10.0, 10.0))
                     .show(ui, |ui| {
-                        ui.label(RichText::new(precipitation.to_string()));
+                        ui.label(RichText::new(precipitation));
                     });
             });
         });
 }
 
-fn human_hour(date: &DateTime<Local>) -> String {
-    let now = Local::now();
+fn current_card(ui: &mut Ui, current: &Observation, units: &UnitSettings) {
+    render_current(
+        ui,
+        current.condition,
+        &current.temperature.convert(units.temperature).to_string(),
+        &format_precipitation(current.precipitation, units),
+        &current.wind_speed.convert(units.speed).to_string(),
+    );
+}
+
+/// Time zone forecast times are shown in
+fn display_offset(forecast_time: &ForecastTime, forecast: &Forecast) -> FixedOffset {
+    match forecast_time {
+        ForecastTime::Location => forecast.utc_offset(),
+        ForecastTime::System => *Local::now().offset(),
+    }
+}
+
+fn hour_card(ui: &mut Ui, time: &DateTime<FixedOffset>, point: &HourlyPoint, units: &UnitSettings) {
+    render_hour(
+        ui,
+        &human_hour(time),
+        point.condition,
+        &point.temperature.convert(units.temperature).to_string(),
+        &format_precipitation(point.precipitation, units),
+        &point.wind_speed.convert(units.speed).to_string(),
+    );
+}
+
+fn day_card(ui: &mut Ui, point: &DailyPoint, units: &UnitSettings, offset: &FixedOffset) {
+    render_day(
+        ui,
+        &human_day(&point.date, offset),
+        &point.temperature_max.convert(units.temperature).to_string(),
+        &point.temperature_min.convert(units.temperature).to_string(),
+        &point.wind_speed_max.convert(units.speed).to_string(),
+        &format_precipitation(point.precipitation_sum, units),
+    );
+}
+
+fn format_precipitation(precipitation: Option<Precipitation>, units: &UnitSettings) -> String {
+    precipitation.map_or(String::from("-"), |p| {
+        p.convert(units.precipitation).to_string()
+    })
+}
+
+fn human_hour(date: &DateTime<FixedOffset>) -> String {
+    let now = Utc::now().with_timezone(date.offset());
     if date.date_naive() == now.date_naive() && date.hour() == now.hour() {
         String::from(t!("now"))
     } else {
@@ -193,11 +412,11 @@ fn human_hour(date: &DateTime<Local>) -> String {
     }
 }
 
-fn human_day(date: &DateTime<Local>) -> String {
-    let today = Local::now().date_naive();
-    if date.date_naive() == today {
+fn human_day(date: &NaiveDate, offset: &FixedOffset) -> String {
+    let today = Utc::now().with_timezone(offset).date_naive();
+    if *date == today {
         String::from(t!("today"))
-    } else if date.date_naive() == today + Duration::days(1) {
+    } else if *date == today + Duration::days(1) {
         String::from(t!("tomorrow"))
     } else {
         // TODO: better date formatting
@@ -217,86 +436,34 @@ impl eframe::App for ForecastWindow {
             .fill(style.visuals.panel_fill);
 
         egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
-            if self.loading {
-                match self.rx.try_recv() {
-                    Ok(response) => {
-                        self.loading = false;
-                        match response {
-                            Ok(weather_response) => self.weather_response = Some(weather_response),
-                            Err(e) => todo!("Could not get forecast: {}", e),
-                        }
-                    }
-                    Err(_) => (),
-                };
-
-                ui.with_layout(
-                    Layout::centered_and_justified(egui::Direction::LeftToRight),
-                    |ui| {
-                        ui.label(t!("loading"));
-                    },
-                );
-            } else {
-                if let Some(ref weather_response) = self.weather_response {
-                    // ui.label(format!("{weather_response:?}"));
-                    ui.vertical(|ui| {
-                        // current weather
-                        if let Some(ref cur) = weather_response.current {
-                            render_current(
-                                ui,
-                                cur.weather_code,
-                                cur.temperature_2m,
-                                cur.precipitation,
-                                cur.wind_speed_10m,
-                            );
-                        }
+            if self.settings.locations.len() > 1 {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.view, ForecastView::Forecast, t!("forecast"));
+                    ui.selectable_value(&mut self.view, ForecastView::Compare, t!("compare"));
+                });
+            }
 
-                        // hourly forecast
-                        ui.horizontal_top(|ui| {
-                            if let Some(ref hourly) = weather_response.hourly {
-                                for (i, time) in hourly.time.iter().enumerate() {
-                                    render_hour(
-                                        ui,
-                                        &human_hour(&Local.from_local_datetime(&time).unwrap()),
-                                        hourly.weather_code[i],
-                                        hourly.temperature_2m[i],
-                                        hourly.precipitation[i],
-                                        hourly.wind_speed_10m[i],
-                                    );
-                                }
-                            }
-                        });
-
-                        // daily forecast
-                        ui.horizontal_top(|ui| {
-                            if let Some(ref daily) = weather_response.daily {
-                                for (i, time) in daily.time.iter().enumerate() {
-                                    render_day(
-                                        ui,
-                                        &human_day(
-                                            &Local
-                                                .from_local_datetime(
-                                                    &time.and_hms_opt(0, 0, 0).unwrap(),
-                                                )
-                                                .unwrap(),
-                                        ),
-                                        &daily.temperature_2m_max[i].to_string(),
-                                        &daily.temperature_2m_min[i].to_string(),
-                                        &daily.wind_speed_10m_max[i].to_string(),
-                                        &daily.precipitation_sum[i].to_string(),
-                                    );
-                                }
-                            }
-                        });
-                    });
+            match self.view {
+                ForecastView::Forecast => self.render_forecast(ui),
+                ForecastView::Compare => {
+                    if self.comparison.is_none() {
+                        self.update_comparison(ctx);
+                    }
+                    self.render_comparison(ui);
                 }
             }
         });
     }
 }
 
-pub(crate) fn show_forecast_window(settings: &Settings) -> Result<()> {
-    let mut forecast_window = ForecastWindow::new(settings.clone());
-    forecast_window.update_weather();
+pub(crate) fn show_forecast_window(
+    forecast: Arc<Mutex<Option<CachedForecast>>>,
+    error: Arc<Mutex<Option<String>>>,
+    pending: Option<PendingForecast>,
+    settings: &Settings,
+    provider: Arc<dyn WeatherProvider>,
+) -> Result<()> {
+    let forecast_window = ForecastWindow::new(forecast, error, pending, settings.clone(), provider);
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_inner_size([480.0, 320.0]),
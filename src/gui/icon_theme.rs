@@ -0,0 +1,508 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+    time::SystemTime,
+};
+
+use directories::ProjectDirs;
+use image::{imageops, RgbaImage};
+use log::{debug, warn};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    units::UnitSettings,
+    weather::{
+        fit_image, get_icon_image, render_svg_image, tray_icon_size, Daylight, Observation,
+        WeatherCondition, ERROR_ICON,
+    },
+};
+
+use super::{
+    color_scheme::ColorScheme,
+    text_icon::{condition_size, render_text},
+};
+
+/// Themes embedded in `assets/weathericons`
+const EMBEDDED_THEMES: [&str; 2] = ["metno", "monochrome"];
+const DEFAULT_THEME: &str = "metno";
+/// Theme drawn in black, inverted for dark taskbars
+const MONOCHROME_THEME: &str = "monochrome";
+
+/// Themes showing the temperature as text instead of an image
+const TEMPERATURE_THEME: &str = "temperature";
+const TEMPERATURE_CONDITION_THEME: &str = "temperature_condition";
+
+/// File name of the manifest in each installed theme directory
+const MANIFEST_FILE: &str = "theme.toml";
+
+/// Manifest as loaded with the modification time of its file
+type LoadedManifest = (SystemTime, Arc<ThemeManifest>);
+
+/// Manifests of installed themes by their directory, read again only after they are edited
+/// instead of for every icon
+static MANIFESTS: LazyLock<Mutex<HashMap<PathBuf, LoadedManifest>>> =
+    LazyLock::new(Default::default);
+
+/// Icon theme, either embedded or installed in [IconTheme::themes_dir]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub(crate) struct IconTheme(String);
+
+/// Manifest `theme.toml` of an installed icon theme
+///
+/// ```toml
+/// name = "My theme"
+///
+/// [icons]
+/// clearsky_day = "sun.png"
+/// clearsky_night = "moon.svg"
+/// lightrain = "drizzle.ico"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct ThemeManifest {
+    pub name: Option<String>,
+    /// Icon files by icon name like `clearsky_night`, relative to the theme directory
+    #[serde(default)]
+    pub icons: HashMap<String, PathBuf>,
+}
+
+impl Default for IconTheme {
+    fn default() -> Self {
+        IconTheme(DEFAULT_THEME.into())
+    }
+}
+
+impl From<String> for IconTheme {
+    fn from(value: String) -> Self {
+        // Settings written before installable themes contain the enum variant names
+        match value.as_str() {
+            "Metno" | "Monochrome" => IconTheme(value.to_lowercase()),
+            _ => IconTheme(value),
+        }
+    }
+}
+
+impl From<IconTheme> for String {
+    fn from(value: IconTheme) -> Self {
+        value.0
+    }
+}
+
+impl Display for IconTheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl ThemeManifest {
+    fn load(theme_dir: &Path) -> Result<Self> {
+        let manifest_string = fs::read_to_string(theme_dir.join(MANIFEST_FILE))?;
+        Ok(toml::from_str(&manifest_string)?)
+    }
+
+    /// The manifest in `theme_dir` as last loaded, loading it again if it changed since
+    fn cached(theme_dir: &Path) -> Result<Arc<Self>> {
+        let modified = fs::metadata(theme_dir.join(MANIFEST_FILE))?.modified()?;
+        if let Some((loaded, manifest)) = MANIFESTS.lock().unwrap().get(theme_dir) {
+            if *loaded == modified {
+                return Ok(manifest.clone());
+            }
+        }
+        let manifest = Arc::new(Self::load(theme_dir)?);
+        MANIFESTS
+            .lock()
+            .unwrap()
+            .insert(theme_dir.to_path_buf(), (modified, manifest.clone()));
+        Ok(manifest)
+    }
+}
+
+impl IconTheme {
+    /// Directory users install their own themes to, one subdirectory per theme
+    pub fn themes_dir() -> Result<PathBuf> {
+        let proj_dirs = ProjectDirs::from("de", "osor", "TrayWeather")
+            .ok_or(Error::other("Failed to get settings directory."))?;
+        Ok(proj_dirs.config_dir().join("themes"))
+    }
+
+    fn is_embedded(&self) -> bool {
+        EMBEDDED_THEMES.contains(&self.0.as_str())
+    }
+
+    fn is_text(&self) -> bool {
+        [TEMPERATURE_THEME, TEMPERATURE_CONDITION_THEME].contains(&self.0.as_str())
+    }
+
+    /// Embedded and text themes followed by installed ones, each with its display name
+    pub fn list() -> Vec<(IconTheme, String)> {
+        let mut themes: Vec<_> = EMBEDDED_THEMES
+            .iter()
+            .map(|name| (IconTheme(name.to_string()), name.to_string()))
+            .collect();
+        themes.push((
+            IconTheme(TEMPERATURE_THEME.into()),
+            t!("temperature_icon").to_string(),
+        ));
+        themes.push((
+            IconTheme(TEMPERATURE_CONDITION_THEME.into()),
+            t!("temperature_condition_icon").to_string(),
+        ));
+        if let Ok(themes_dir) = Self::themes_dir() {
+            themes.extend(Self::installed_in(&themes_dir));
+        }
+        themes
+    }
+
+    /// Subdirectories of `themes_dir` containing a manifest
+    fn installed_in(themes_dir: &Path) -> Vec<(IconTheme, String)> {
+        let Ok(entries) = fs::read_dir(themes_dir) else {
+            return vec![];
+        };
+        let mut themes: Vec<_> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let id = entry.file_name().into_string().ok()?;
+                let manifest = match ThemeManifest::load(&entry.path()) {
+                    Ok(manifest) => manifest,
+                    Err(err) => {
+                        warn!("Skipping icon theme {id}: {err}");
+                        return None;
+                    }
+                };
+                let name = manifest.name.unwrap_or_else(|| id.clone());
+                Some((IconTheme(id), name))
+            })
+            .filter(|(theme, _)| !theme.is_embedded() && !theme.is_text())
+            .collect();
+        themes.sort_by(|a, b| a.1.cmp(&b.1));
+        themes
+    }
+
+    /// Image showing `weather`, either as icon or as temperature text
+    ///
+    /// Monochrome icons are only inverted on taskbars known to be dark.
+    pub fn weather_image(
+        &self,
+        weather: &Observation,
+        units: &UnitSettings,
+        color_scheme: Option<ColorScheme>,
+    ) -> Result<RgbaImage> {
+        let size = tray_icon_size();
+        let (condition, daylight) = (&weather.condition, weather.daylight);
+        if self.0 == MONOCHROME_THEME && color_scheme == Some(ColorScheme::Dark) {
+            let mut image = self.image(condition, daylight, size)?;
+            imageops::invert(&mut image);
+            return Ok(image);
+        }
+        if !self.is_text() {
+            return self.image(condition, daylight, size);
+        }
+        let condition = match self.0.as_str() {
+            TEMPERATURE_CONDITION_THEME => {
+                Some(IconTheme::default().image(condition, daylight, condition_size(size))?)
+            }
+            _ => None,
+        };
+        // Cast to avoid showing "-0°"
+        let temperature = weather.temperature.convert(units.temperature).value.round() as i32;
+        render_text(&format!("{temperature}°"), condition.as_ref(), size)
+    }
+
+    /// Load the icon of `condition`, falling back to its base icon, the default theme and
+    /// finally the error icon, so a missing or broken file never fails an update
+    pub fn image(
+        &self,
+        condition: &WeatherCondition,
+        daylight: Daylight,
+        size: u32,
+    ) -> Result<RgbaImage> {
+        let themes_dir = Self::themes_dir();
+        self.image_in(themes_dir.as_deref().ok(), condition, daylight, size)
+    }
+
+    /// [IconTheme::image] with installed themes looked up in `themes_dir`
+    fn image_in(
+        &self,
+        themes_dir: Option<&Path>,
+        condition: &WeatherCondition,
+        daylight: Daylight,
+        size: u32,
+    ) -> Result<RgbaImage> {
+        let default_theme = IconTheme::default();
+        let themes = if *self == default_theme {
+            vec![self]
+        } else {
+            vec![self, &default_theme]
+        };
+        let icon_names = icon_names(condition, daylight);
+        for theme in themes {
+            for icon_name in &icon_names {
+                match theme.own_image(themes_dir, icon_name, size) {
+                    Ok(image) => return Ok(image),
+                    Err(err) => debug!("No icon {icon_name} in theme {theme}: {err}"),
+                }
+            }
+        }
+        warn!("No icon for {condition:?} in theme {self}, using error icon");
+        get_icon_image(ERROR_ICON, size)
+    }
+
+    /// Load the icon named like `clearsky_night` from this theme only
+    fn own_image(
+        &self,
+        themes_dir: Option<&Path>,
+        icon_name: &str,
+        size: u32,
+    ) -> Result<RgbaImage> {
+        if self.is_embedded() {
+            return get_icon_image(
+                &format!("weathericons/{}/svg/{}.svg", self.0, icon_name),
+                size,
+            );
+        }
+        if self.is_text() {
+            return Err(Error::other(format!("Theme {self} has no icons.")));
+        }
+        let themes_dir = themes_dir.ok_or(Error::other("Failed to get themes directory."))?;
+        self.installed_image(&themes_dir.join(&self.0), icon_name, size)
+    }
+
+    fn installed_image(&self, theme_dir: &Path, icon_name: &str, size: u32) -> Result<RgbaImage> {
+        let manifest = ThemeManifest::cached(theme_dir)?;
+        let file = manifest
+            .icons
+            .get(icon_name)
+            .ok_or_else(|| Error::other(format!("No icon {icon_name} in manifest.")))?;
+        load_image_file(&theme_dir.join(file), size)
+    }
+}
+
+/// Generic icon for conditions with thunder, where a theme lacks the specific one
+const THUNDER_ICON_NAME: &str = "heavyrainandthunder";
+
+/// Icon names for `condition` from the most specific one to the base condition
+fn icon_names(condition: &WeatherCondition, daylight: Daylight) -> Vec<String> {
+    let mut icon_names = vec![];
+    let thunder = condition
+        .has_thunder()
+        .then(|| THUNDER_ICON_NAME.to_string());
+    for icon_name in [
+        Some(condition.icon_name(daylight)),
+        Some(condition.base_icon_name().to_string()),
+        Some(condition.icon_name(Daylight::Day)),
+        thunder,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        if !icon_names.contains(&icon_name) {
+            icon_names.push(icon_name);
+        }
+    }
+    icon_names
+}
+
+/// Load an ICO, PNG or SVG file as a square bitmap of `size` pixels
+fn load_image_file(path: &Path, size: u32) -> Result<RgbaImage> {
+    let is_svg = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("svg"));
+    if is_svg {
+        return render_svg_image(&fs::read(path)?, size);
+    }
+    let img = image::open(path).map_err(Error::other)?;
+    Ok(fit_image(img.to_rgba8(), size))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::UNIX_EPOCH};
+
+    use crate::weather::{get_icon_image, Daylight, Intensity, WeatherCondition, ERROR_ICON};
+
+    use super::{
+        icon_names, IconTheme, ThemeManifest, EMBEDDED_THEMES, TEMPERATURE_CONDITION_THEME,
+        TEMPERATURE_THEME,
+    };
+
+    /// Every condition with every intensity
+    fn all_conditions() -> Vec<WeatherCondition> {
+        use WeatherCondition::*;
+        let mut conditions = vec![
+            ClearSky,
+            MainlyClear,
+            PartlyCloudy,
+            Overcast,
+            Fog,
+            RimeFog,
+            SnowGrains,
+            Thunderstorm,
+            Unknown,
+        ];
+        for intensity in [Intensity::Light, Intensity::Moderate, Intensity::Heavy] {
+            conditions.extend([
+                Drizzle(intensity),
+                FreezingDrizzle(intensity),
+                Rain(intensity),
+                FreezingRain(intensity),
+                Sleet(intensity),
+                Snow(intensity),
+                RainShowers(intensity),
+                SnowShowers(intensity),
+                ThunderstormWithHail(intensity),
+                RainAndThunder(intensity),
+                SleetAndThunder(intensity),
+                SnowAndThunder(intensity),
+            ]);
+        }
+        conditions
+    }
+
+    /// Fails to compile when a variant is added, as a reminder to add it to [all_conditions]
+    const _: fn(WeatherCondition) = |condition| {
+        use WeatherCondition::*;
+        match condition {
+            ClearSky | MainlyClear | PartlyCloudy | Overcast | Fog | RimeFog | SnowGrains
+            | Thunderstorm | Unknown => (),
+            Drizzle(_)
+            | FreezingDrizzle(_)
+            | Rain(_)
+            | FreezingRain(_)
+            | Sleet(_)
+            | Snow(_)
+            | RainShowers(_)
+            | SnowShowers(_)
+            | ThunderstormWithHail(_)
+            | RainAndThunder(_)
+            | SleetAndThunder(_)
+            | SnowAndThunder(_) => (),
+        }
+    };
+
+    #[test]
+    fn complete_themes() {
+        use WeatherCondition::Unknown;
+
+        let daylights = [Daylight::Day, Daylight::Night, Daylight::PolarTwilight];
+        for id in EMBEDDED_THEMES {
+            let theme = IconTheme(id.into());
+            // Unknown weather is shown with the error icon
+            for condition in all_conditions().into_iter().filter(|c| *c != Unknown) {
+                for daylight in daylights {
+                    let icon_name = condition.icon_name(daylight);
+                    assert!(
+                        theme.own_image(None, &icon_name, 22).is_ok(),
+                        "{theme}: {icon_name}"
+                    );
+                }
+            }
+        }
+        for id in EMBEDDED_THEMES
+            .into_iter()
+            .chain([TEMPERATURE_THEME, TEMPERATURE_CONDITION_THEME])
+        {
+            let theme = IconTheme(id.into());
+            for condition in all_conditions() {
+                for daylight in daylights {
+                    let image = theme.image_in(None, &condition, daylight, 22).unwrap();
+                    assert_eq!(image.dimensions(), (22, 22), "{theme}: {condition:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fall_back_to_error_icon() {
+        let themes_dir = tempfile::tempdir().unwrap();
+        let themes_dir = Some(themes_dir.path());
+        let error_icon = get_icon_image(ERROR_ICON, 22).unwrap();
+        let theme = IconTheme("missing".into());
+        let image = theme
+            .image_in(themes_dir, &WeatherCondition::Unknown, Daylight::Night, 22)
+            .unwrap();
+        assert_eq!(image, error_icon);
+        // Falls back to the default theme
+        let image = theme
+            .image_in(themes_dir, &WeatherCondition::Overcast, Daylight::Night, 22)
+            .unwrap();
+        assert_ne!(image, error_icon);
+        assert_eq!(
+            icon_names(&WeatherCondition::ClearSky, Daylight::Night),
+            ["clearsky_night", "clearsky", "clearsky_day"]
+        );
+        assert_eq!(
+            icon_names(&WeatherCondition::Overcast, Daylight::Night),
+            ["cloudy"]
+        );
+        assert_eq!(
+            icon_names(
+                &WeatherCondition::SnowAndThunder(Intensity::Heavy),
+                Daylight::Day
+            ),
+            ["heavysleetandthunder", "heavyrainandthunder"]
+        );
+    }
+
+    #[test]
+    fn migrate_theme_names() {
+        let theme: IconTheme = serde_json::from_str(r#""Monochrome""#).unwrap();
+        assert_eq!(theme, IconTheme("monochrome".into()));
+        let theme: IconTheme = serde_json::from_str(r#""my-theme""#).unwrap();
+        assert_eq!(serde_json::to_string(&theme).unwrap(), r#""my-theme""#);
+    }
+
+    #[test]
+    fn load_installed_themes() {
+        let themes_dir = tempfile::tempdir().unwrap();
+        let themes_dir = themes_dir.path();
+        let theme_dir = themes_dir.join("plain");
+        fs::create_dir_all(&theme_dir).unwrap();
+        fs::create_dir_all(themes_dir.join("no-manifest")).unwrap();
+        fs::write(
+            theme_dir.join("theme.toml"),
+            r#"
+            name = "Plain"
+
+            [icons]
+            clearsky_day = "sun.png"
+            clearsky_night = "moon.svg"
+            "#,
+        )
+        .unwrap();
+        image::RgbaImage::new(16, 16)
+            .save(theme_dir.join("sun.png"))
+            .unwrap();
+        fs::write(
+            theme_dir.join("moon.svg"),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 30 30"><circle cx="15" cy="15" r="10"/></svg>"#,
+        )
+        .unwrap();
+
+        let themes = IconTheme::installed_in(themes_dir);
+        assert_eq!(themes, vec![(IconTheme("plain".into()), "Plain".into())]);
+
+        let theme = &themes[0].0;
+        let manifest = ThemeManifest::load(&theme_dir).unwrap();
+        assert_eq!(manifest.icons.len(), 2);
+        let image = theme
+            .installed_image(&theme_dir, "clearsky_day", 22)
+            .unwrap();
+        assert_eq!(image.dimensions(), (22, 22));
+        assert!(theme
+            .installed_image(&theme_dir, "clearsky_night", 22)
+            .is_ok());
+        assert!(theme.installed_image(&theme_dir, "fog", 22).is_err());
+
+        // Edits to the manifest are picked up despite the cache
+        let manifest_file = fs::File::create(theme_dir.join("theme.toml")).unwrap();
+        manifest_file.set_modified(UNIX_EPOCH).unwrap();
+        assert!(theme
+            .installed_image(&theme_dir, "clearsky_night", 22)
+            .is_err());
+    }
+}
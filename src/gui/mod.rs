@@ -1,30 +1,39 @@
-use std::slice::Iter;
+use std::{fmt::Display, slice::Iter};
 
+use rust_i18n::t;
 use serde::{Deserialize, Serialize};
 
+pub mod badge;
+pub mod color_scheme;
 pub mod forecast_window;
+pub mod icon_theme;
 pub mod settings_window;
+pub mod text_icon;
 pub mod weather_tray_icon;
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub enum IconTheme {
-    Metno,
-    Monochrome,
+/// Time zone the forecast window shows times in
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ForecastTime {
+    /// Local time of the forecast's location
+    #[default]
+    Location,
+    /// Time zone of this computer
+    System,
 }
 
-impl ToString for IconTheme {
-    fn to_string(&self) -> String {
+impl Display for ForecastTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            IconTheme::Metno => String::from("metno"),
-            IconTheme::Monochrome => String::from("monochrome"),
+            ForecastTime::Location => f.write_str(&t!("forecast_time_location")),
+            ForecastTime::System => f.write_str(&t!("forecast_time_system")),
         }
     }
 }
 
-impl IconTheme {
-    pub fn iterator() -> Iter<'static, IconTheme> {
-        use IconTheme::*;
-        static ICON_THEMES: [IconTheme; 2] = [Metno, Monochrome];
-        ICON_THEMES.iter()
+impl ForecastTime {
+    pub fn iterator() -> Iter<'static, ForecastTime> {
+        use ForecastTime::*;
+        static FORECAST_TIMES: [ForecastTime; 2] = [Location, System];
+        FORECAST_TIMES.iter()
     }
 }
@@ -1,53 +1,62 @@
 use std::sync::mpsc::{channel, Receiver, Sender};
 
-use eframe::egui::{self, Checkbox, ComboBox, TextEdit, Ui};
+use eframe::egui::{self, Button, Checkbox, ComboBox, TextEdit, Ui};
 use rust_i18n::t;
 
 use crate::{
+    http::{create_client, create_client_or_default},
+    provider::{create_provider, ProviderKind},
     settings::Settings,
-    weather::{search_location, Location},
+    units::{PrecipitationUnit, SpeedUnit, TemperatureUnit},
+    weather::Location,
     Result, PROGRAM_NAME,
 };
 
-use super::IconTheme;
+use super::{color_scheme::ColorSchemeSetting, icon_theme::IconTheme, ForecastTime};
 
 enum SettingsScreen {
     Home,
     Location,
 }
 
+/// Change to the list of saved locations
+enum LocationAction {
+    Activate(usize),
+    Pin(usize, bool),
+    MoveDown(usize),
+    Remove(usize),
+}
+
 pub(crate) struct SettingsWindow {
     tx_window: Option<Sender<Option<Settings>>>,
     rx_locations: Receiver<Result<Vec<Location>>>,
     tx_locations: Sender<Result<Vec<Location>>>,
     settings: Settings,
+    /// Available icon themes with their display names
+    icon_themes: Vec<(IconTheme, String)>,
     location_name: String,
     found_locations: Option<Vec<Location>>,
+    /// Why the last location search failed
+    search_error: Option<String>,
     screen: SettingsScreen,
-}
-
-impl Default for SettingsWindow {
-    fn default() -> Self {
-        let locations_channel = channel();
-        Self {
-            tx_window: None,
-            rx_locations: locations_channel.1,
-            tx_locations: locations_channel.0,
-            settings: Settings::default(),
-            location_name: "".into(),
-            found_locations: None,
-            screen: SettingsScreen::Home,
-        }
-    }
+    /// Why the HTTP settings could not be saved
+    http_error: Option<String>,
 }
 
 impl SettingsWindow {
     pub fn new(tx: Sender<Option<Settings>>, settings: &Settings) -> Self {
+        let (tx_locations, rx_locations) = channel();
         SettingsWindow {
             tx_window: Some(tx),
+            rx_locations,
+            tx_locations,
             settings: settings.clone(),
+            icon_themes: IconTheme::list(),
+            location_name: "".into(),
+            found_locations: None,
+            search_error: None,
             screen: SettingsScreen::Home,
-            ..Default::default()
+            http_error: None,
         }
     }
 }
@@ -66,10 +75,9 @@ impl SettingsWindow {
         ui.heading(t!("settings_heading"));
 
         setting_entry(ui, t!("location"), |ui| {
-            let text = if self.settings.location.id != 0 {
-                self.settings.location.to_human_readable()
-            } else {
-                t!("empty_location").into()
+            let text = match self.settings.location() {
+                Some(location) => location.to_human_readable(),
+                None => t!("empty_location").into(),
             };
             if ui.button(text).clicked() {
                 self.screen = SettingsScreen::Location;
@@ -81,20 +89,133 @@ impl SettingsWindow {
         });
 
         setting_entry(ui, t!("icon_theme"), |ui| {
+            let selected = self
+                .icon_themes
+                .iter()
+                .find(|(icon_theme, _)| *icon_theme == self.settings.icon_theme)
+                .map_or(self.settings.icon_theme.to_string(), |(_, name)| {
+                    name.clone()
+                });
             ComboBox::from_id_source("icon_theme")
-                .selected_text(&self.settings.icon_theme.to_string())
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    self.icon_themes
+                        .iter()
+                        .cloned()
+                        .for_each(|(icon_theme, name)| {
+                            ui.selectable_value(&mut self.settings.icon_theme, icon_theme, name);
+                        });
+                });
+        });
+
+        setting_entry(ui, t!("color_scheme"), |ui| {
+            ComboBox::from_id_source("color_scheme")
+                .selected_text(self.settings.color_scheme.to_string())
+                .show_ui(ui, |ui| {
+                    ColorSchemeSetting::iterator()
+                        .cloned()
+                        .for_each(|color_scheme| {
+                            let text = color_scheme.to_string();
+                            ui.selectable_value(
+                                &mut self.settings.color_scheme,
+                                color_scheme,
+                                text,
+                            );
+                        });
+                });
+        });
+
+        setting_entry(ui, t!("forecast_time"), |ui| {
+            ComboBox::from_id_source("forecast_time")
+                .selected_text(self.settings.forecast_time.to_string())
+                .show_ui(ui, |ui| {
+                    ForecastTime::iterator().cloned().for_each(|forecast_time| {
+                        let text = forecast_time.to_string();
+                        ui.selectable_value(&mut self.settings.forecast_time, forecast_time, text);
+                    });
+                });
+        });
+
+        setting_entry(ui, t!("provider"), |ui| {
+            ComboBox::from_id_source("provider")
+                .selected_text(self.settings.provider.to_string())
                 .show_ui(ui, |ui| {
-                    IconTheme::iterator().cloned().for_each(|icon_theme| {
-                        let text = icon_theme.to_string();
-                        ui.selectable_value(&mut self.settings.icon_theme, icon_theme, text);
+                    ProviderKind::iterator().cloned().for_each(|provider| {
+                        let text = provider.to_string();
+                        ui.selectable_value(&mut self.settings.provider, provider, text);
                     });
                 });
         });
 
+        setting_entry(ui, t!("temperature_unit"), |ui| {
+            ComboBox::from_id_source("temperature_unit")
+                .selected_text(self.settings.units.temperature.to_string())
+                .show_ui(ui, |ui| {
+                    TemperatureUnit::iterator().cloned().for_each(|unit| {
+                        let text = unit.to_string();
+                        ui.selectable_value(&mut self.settings.units.temperature, unit, text);
+                    });
+                });
+        });
+
+        setting_entry(ui, t!("speed_unit"), |ui| {
+            ComboBox::from_id_source("speed_unit")
+                .selected_text(self.settings.units.speed.to_string())
+                .show_ui(ui, |ui| {
+                    SpeedUnit::iterator().cloned().for_each(|unit| {
+                        let text = unit.to_string();
+                        ui.selectable_value(&mut self.settings.units.speed, unit, text);
+                    });
+                });
+        });
+
+        setting_entry(ui, t!("precipitation_unit"), |ui| {
+            ComboBox::from_id_source("precipitation_unit")
+                .selected_text(self.settings.units.precipitation.to_string())
+                .show_ui(ui, |ui| {
+                    PrecipitationUnit::iterator().cloned().for_each(|unit| {
+                        let text = unit.to_string();
+                        ui.selectable_value(&mut self.settings.units.precipitation, unit, text);
+                    });
+                });
+        });
+
+        setting_entry(ui, t!("open_meteo_forecast_url"), |ui| {
+            ui.text_edit_singleline(&mut self.settings.open_meteo.forecast_url);
+        });
+
+        setting_entry(ui, t!("open_meteo_geocoding_url"), |ui| {
+            ui.text_edit_singleline(&mut self.settings.open_meteo.geocoding_url);
+        });
+
+        setting_entry(ui, t!("open_meteo_api_key"), |ui| {
+            ui.add(TextEdit::singleline(&mut self.settings.open_meteo.api_key).password(true));
+        });
+
+        setting_entry(ui, t!("proxy"), |ui| {
+            ui.text_edit_singleline(&mut self.settings.http.proxy);
+        });
+
+        setting_entry(ui, t!("ca_certificate"), |ui| {
+            ui.text_edit_singleline(&mut self.settings.http.ca_certificate);
+        });
+
+        setting_entry(ui, t!("connect_timeout"), |ui| {
+            integer_edit_field(ui, &mut self.settings.http.connect_timeout)
+        });
+
+        setting_entry(ui, t!("read_timeout"), |ui| {
+            integer_edit_field(ui, &mut self.settings.http.read_timeout)
+        });
+
         setting_entry(ui, t!("autostart", name = PROGRAM_NAME), |ui| {
             ui.add(Checkbox::without_text(&mut self.settings.autorun_enabled));
         });
 
+        if let Some(error) = &self.http_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
         ui.horizontal(|ui| {
             ui.with_layout(egui::Layout::right_to_left(egui::Align::BOTTOM), |ui| {
                 // reversed because of right_to_left
@@ -102,6 +223,11 @@ impl SettingsWindow {
                 let save_button = ui.button(t!("dialog.save"));
 
                 if save_button.clicked() {
+                    // Keep the window open rather than saving settings no client can be built with
+                    if let Err(err) = create_client(&self.settings.http) {
+                        self.http_error = Some(t!("invalid_http_settings", error = err).into());
+                        return;
+                    }
                     if let Some(tx) = &self.tx_window {
                         tx.send(Some(self.settings.clone())).unwrap();
                     }
@@ -114,14 +240,63 @@ impl SettingsWindow {
     }
 
     fn location_screen(&mut self, ui: &mut Ui) {
-        match self.rx_locations.try_recv() {
-            Ok(response) => match response {
-                Ok(found_locations) => self.found_locations = Some(found_locations),
-                Err(e) => todo!("Could not get locations: {}", e),
-            },
-            Err(_) => (),
+        if let Ok(response) = self.rx_locations.try_recv() {
+            match response {
+                Ok(found_locations) => {
+                    self.found_locations = Some(found_locations);
+                    self.search_error = None;
+                }
+                Err(err) => {
+                    self.found_locations = None;
+                    self.search_error = Some(t!("search_error", error = err).into());
+                }
+            }
         }
 
+        ui.heading(t!("locations"));
+
+        let mut action = None;
+        let count = self.settings.locations.len();
+        for (index, location) in self.settings.locations.iter().enumerate() {
+            ui.horizontal(|ui| {
+                let active = index == self.settings.active_location;
+                if ui.radio(active, location.to_human_readable()).clicked() {
+                    action = Some(LocationAction::Activate(index));
+                }
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    // reversed because of right_to_left
+                    if ui.button("✖").clicked() {
+                        action = Some(LocationAction::Remove(index));
+                    }
+                    if ui
+                        .add_enabled(index + 1 < count, Button::new("▼"))
+                        .clicked()
+                    {
+                        action = Some(LocationAction::MoveDown(index));
+                    }
+                    if ui.add_enabled(index > 0, Button::new("▲")).clicked() {
+                        action = Some(LocationAction::MoveDown(index - 1));
+                    }
+                    let pinned = self.settings.is_pinned(location);
+                    if ui.selectable_label(pinned, t!("pin")).clicked() {
+                        action = Some(LocationAction::Pin(index, !pinned));
+                    }
+                });
+            });
+        }
+        match action {
+            Some(LocationAction::Activate(index)) => self.settings.active_location = index,
+            Some(LocationAction::Pin(index, pinned)) => {
+                let location = self.settings.locations[index].clone();
+                self.settings.set_pinned(&location, pinned);
+            }
+            Some(LocationAction::MoveDown(index)) => self.settings.move_location_down(index),
+            Some(LocationAction::Remove(index)) => self.settings.remove_location(index),
+            None => (),
+        }
+
+        ui.separator();
+
         ui.heading(t!("location_heading"));
 
         ui.horizontal(|ui| {
@@ -137,13 +312,20 @@ impl SettingsWindow {
             if ui.button(t!("search_location")).clicked() {
                 let name: String = self.location_name.clone();
                 let tx = self.tx_locations.clone();
+                // Search with the provider and geocoding URL as edited, not yet saved
+                let client = create_client_or_default(&self.settings.http);
+                let provider = create_provider(&self.settings, client);
                 tokio::spawn(async move {
-                    let results = search_location(&name, "de").await;
+                    let results = provider.search_location(&name, "de").await;
                     tx.send(results).unwrap();
                 });
             }
         });
 
+        if let Some(error) = &self.search_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
         ui.separator();
 
         ui.horizontal(|ui| {
@@ -151,13 +333,20 @@ impl SettingsWindow {
                 ui.vertical(|ui| {
                     for location in locations {
                         if ui.button(location.to_human_readable()).clicked() {
-                            self.settings.location = location.clone();
-                            self.screen = SettingsScreen::Home;
+                            self.settings.add_location(location.clone());
                         }
                     }
                 });
             }
         });
+
+        ui.separator();
+
+        if ui.button(t!("dialog.back")).clicked() {
+            self.found_locations = None;
+            self.search_error = None;
+            self.screen = SettingsScreen::Home;
+        }
     }
 }
 
@@ -173,10 +362,12 @@ impl eframe::App for SettingsWindow {
             .fill(style.visuals.panel_fill);
 
         egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
-            match self.screen {
-                SettingsScreen::Home => self.settings_screen(ctx, ui),
-                SettingsScreen::Location => self.location_screen(ui),
-            };
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                match self.screen {
+                    SettingsScreen::Home => self.settings_screen(ctx, ui),
+                    SettingsScreen::Location => self.location_screen(ui),
+                };
+            });
         });
     }
 }
@@ -219,7 +410,7 @@ pub(crate) fn show_settings_window(settings: &Settings) -> Option<Settings> {
     let settings_window = SettingsWindow::new(tx.clone(), settings);
 
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([480.0, 400.0]),
+        viewport: egui::ViewportBuilder::default().with_inner_size([480.0, 640.0]),
         ..Default::default()
     };
     eframe::run_native(
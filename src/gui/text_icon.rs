@@ -0,0 +1,156 @@
+use ab_glyph::{point, Font, FontRef, Glyph, PxScale, Rect, ScaleFont};
+use image::{imageops, Pixel, Rgba, RgbaImage};
+
+use crate::error::{Error, Result};
+
+/// Font of the text icons, see `assets/fonts/LICENSE`
+const FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSansCondensed-Bold.ttf");
+
+const TEXT_COLOR: Rgba<u8> = Rgba([0xff, 0xff, 0xff, 0xff]);
+/// Color of the outline keeping the text readable on light and dark taskbars
+const OUTLINE_COLOR: Rgba<u8> = Rgba([0x20, 0x20, 0x20, 0xff]);
+
+/// Share of the icon height the condition glyph takes in its corner
+const CONDITION_SIZE: f32 = 0.6;
+
+/// Size of the condition glyph drawn in the corner of a text icon of `size` pixels
+pub fn condition_size(size: u32) -> u32 {
+    (size as f32 * CONDITION_SIZE).round() as u32
+}
+
+/// Render `text` into a square bitmap of `size` pixels as large as possible,
+/// bottom-aligned below a condition glyph if given
+pub fn render_text(text: &str, condition: Option<&RgbaImage>, size: u32) -> Result<RgbaImage> {
+    let font = FontRef::try_from_slice(FONT).map_err(Error::other)?;
+    let mut image = RgbaImage::new(size, size);
+    if let Some(condition) = condition {
+        imageops::overlay(
+            &mut image,
+            condition,
+            size.saturating_sub(condition.width()) as i64,
+            0,
+        );
+    }
+
+    let outline = (size / 24).max(1) as i32;
+    let area_width = size as f32 - 2.0 * outline as f32;
+    let area_height = match condition {
+        Some(_) => size as f32 * CONDITION_SIZE,
+        None => size as f32 - 2.0 * outline as f32,
+    };
+
+    // Lay out once to measure, then again at the scale filling the available area
+    let glyphs = layout(&font, text, PxScale::from(size as f32));
+    let Some(bounds) = glyph_bounds(&font, &glyphs) else {
+        return Ok(image);
+    };
+    let fit = (area_width / bounds.width()).min(area_height / bounds.height());
+    let glyphs = layout(&font, text, PxScale::from(size as f32 * fit));
+    let Some(bounds) = glyph_bounds(&font, &glyphs) else {
+        return Ok(image);
+    };
+    let offset_x = ((size as f32 - bounds.width()) / 2.0 - bounds.min.x).round() as i32;
+    let offset_y = match condition {
+        Some(_) => size as f32 - outline as f32 - bounds.max.y,
+        None => (size as f32 - bounds.height()) / 2.0 - bounds.min.y,
+    }
+    .round() as i32;
+
+    let mut coverage = vec![0.0f32; (size * size) as usize];
+    for glyph in glyphs {
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let min = outlined.px_bounds().min;
+        outlined.draw(|x, y, c| {
+            let x = min.x as i32 + x as i32 + offset_x;
+            let y = min.y as i32 + y as i32 + offset_y;
+            if (0..size as i32).contains(&x) && (0..size as i32).contains(&y) {
+                let index = (y as u32 * size + x as u32) as usize;
+                coverage[index] = (coverage[index] + c).min(1.0);
+            }
+        });
+    }
+
+    let coverage_at = |x: i32, y: i32| {
+        if (0..size as i32).contains(&x) && (0..size as i32).contains(&y) {
+            coverage[(y as u32 * size + x as u32) as usize]
+        } else {
+            0.0
+        }
+    };
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let (x, y) = (x as i32, y as i32);
+        let outline_alpha = (-outline..=outline)
+            .flat_map(|dy| (-outline..=outline).map(move |dx| (dx, dy)))
+            .map(|(dx, dy)| coverage_at(x + dx, y + dy))
+            .fold(0.0, f32::max);
+        pixel.blend(&with_alpha(OUTLINE_COLOR, outline_alpha));
+        pixel.blend(&with_alpha(TEXT_COLOR, coverage_at(x, y)));
+    }
+    Ok(image)
+}
+
+/// Position the glyphs of `text` on a single line with the baseline at 0
+fn layout(font: &FontRef, text: &str, scale: PxScale) -> Vec<Glyph> {
+    let font = font.as_scaled(scale);
+    let mut caret = 0.0;
+    let mut previous = None;
+    text.chars()
+        .map(|c| {
+            let id = font.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += font.kern(previous, id);
+            }
+            let glyph = id.with_scale_and_position(scale, point(caret, 0.0));
+            caret += font.h_advance(id);
+            previous = Some(id);
+            glyph
+        })
+        .collect()
+}
+
+/// Pixel bounds of all visible glyphs
+fn glyph_bounds(font: &FontRef, glyphs: &[Glyph]) -> Option<Rect> {
+    glyphs
+        .iter()
+        .filter_map(|glyph| font.outline_glyph(glyph.clone()))
+        .map(|outlined| outlined.px_bounds())
+        .reduce(|a, b| Rect {
+            min: point(a.min.x.min(b.min.x), a.min.y.min(b.min.y)),
+            max: point(a.max.x.max(b.max.x), a.max.y.max(b.max.y)),
+        })
+}
+
+fn with_alpha(color: Rgba<u8>, alpha: f32) -> Rgba<u8> {
+    let [r, g, b, _] = color.0;
+    Rgba([r, g, b, (alpha.clamp(0.0, 1.0) * 255.0).round() as u8])
+}
+
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
+
+    use super::{condition_size, render_text};
+
+    #[test]
+    fn render_temperatures() {
+        for text in ["17°", "-23°", "104°"] {
+            let image = render_text(text, None, 22).unwrap();
+            assert_eq!(image.dimensions(), (22, 22));
+            let visible = image.pixels().filter(|pixel| pixel[3] > 0).count();
+            assert!(visible > 22, "{text}");
+            assert_eq!(image.get_pixel(0, 0)[3], 0, "{text}");
+        }
+    }
+
+    #[test]
+    fn render_over_condition() {
+        let size = condition_size(32);
+        let condition = RgbaImage::from_pixel(size, size, image::Rgba([0, 0, 255, 255]));
+        let image = render_text("5°", Some(&condition), 32).unwrap();
+        assert_eq!(image.get_pixel(31, 0)[2], 255);
+        assert_eq!(image.get_pixel(0, 0)[3], 0);
+        assert!(image.pixels().any(|pixel| pixel.0 == [255, 255, 255, 255]));
+    }
+}
@@ -1,45 +1,80 @@
+use std::cell::Cell;
+
+use chrono::{DateTime, Local, TimeZone, Utc};
 use log::debug;
+use rust_i18n::t;
 use tray_icon::{menu::Menu, TrayIcon, TrayIconBuilder};
 
 use crate::error::Result;
-use crate::weather::{get_icon, CurrentWeather, Location};
+use crate::units::UnitSettings;
+use crate::weather::{get_icon, icon_from_rgba, Location, Observation, ERROR_ICON};
 
-use super::IconTheme;
+use super::{
+    badge::{add_badges, Badge},
+    color_scheme::ColorScheme,
+    icon_theme::IconTheme,
+};
 
 pub(crate) struct WeatherTrayIcon {
     pub tray_icon: TrayIcon,
+    /// Color scheme of the taskbar the icon is shown on, if known
+    color_scheme: Cell<Option<ColorScheme>>,
 }
 
 impl WeatherTrayIcon {
-    pub fn new(menu: Menu) -> Result<Self> {
-        debug!("Building tray menu");
+    pub fn new(id: &str, menu: Option<Menu>) -> Result<Self> {
+        debug!("Building tray icon {id}");
+        let mut builder = TrayIconBuilder::new()
+            .with_id(id)
+            .with_menu_on_left_click(false);
+        if let Some(menu) = menu {
+            builder = builder.with_menu(Box::new(menu));
+        }
         Ok(WeatherTrayIcon {
-            tray_icon: TrayIconBuilder::new()
-                .with_menu(Box::new(menu))
-                .with_menu_on_left_click(false)
-                .build()?,
+            tray_icon: builder.build()?,
+            color_scheme: Cell::new(None),
         })
     }
 
+    /// Set the color scheme used for the next weather shown
+    pub fn set_color_scheme(&self, color_scheme: Option<ColorScheme>) {
+        self.color_scheme.set(color_scheme);
+    }
+
     pub fn set_weather(
         &self,
         location: &Location,
         icon_theme: &IconTheme,
-        weather: &CurrentWeather,
+        units: &UnitSettings,
+        weather: &Observation,
+        badges: &[Badge],
     ) -> Result<()> {
-        debug!("Set weather: {:?}", &weather);
-        let icon_path = format!(
-            "weathericons/{}/ico/{}.ico",
-            icon_theme.to_string(),
-            weather.icon_name()
-        );
-        let icon = get_icon(&icon_path)?;
-        self.tray_icon.set_icon(Some(icon))?;
+        debug!("Set weather: {:?} {:?}", &weather, badges);
+        let mut image = icon_theme.weather_image(weather, units, self.color_scheme.get())?;
+        add_badges(&mut image, badges)?;
+        self.tray_icon.set_icon(Some(icon_from_rgba(image)?))?;
+        self.tray_icon
+            .set_tooltip(Some(weather_tooltip(location, units, weather)))?;
+        Ok(())
+    }
+
+    /// Show cached weather, marking it with the time it was fetched
+    pub fn set_stale_weather(
+        &self,
+        location: &Location,
+        icon_theme: &IconTheme,
+        units: &UnitSettings,
+        weather: &Observation,
+        badges: &[Badge],
+        fetched: &DateTime<Utc>,
+    ) -> Result<()> {
+        let badges = [badges, &[Badge::Stale]].concat();
+        self.set_weather(location, icon_theme, units, weather, &badges)?;
+        let fetched = fetched_time(&fetched.with_timezone(&Local), &Local::now());
         self.tray_icon.set_tooltip(Some(format!(
-            "{}: {} - {}",
-            location.name,
-            weather.temperature,
-            weather.description()
+            "{} ({})",
+            weather_tooltip(location, units, weather),
+            t!("stale", time = fetched)
         )))?;
         Ok(())
     }
@@ -47,8 +82,45 @@ impl WeatherTrayIcon {
     pub fn set_error(&self, msg: &str) -> Result<()> {
         debug!("Set error: {}", msg);
         self.tray_icon.set_tooltip(Some(msg))?;
-        let icon = get_icon("tabler-icons/exclamation-circle.ico")?;
+        let icon = get_icon(ERROR_ICON)?;
         self.tray_icon.set_icon(Some(icon))?;
         Ok(())
     }
 }
+
+/// Time of `fetched`, with the date if it is not the day of `now`
+fn fetched_time<Tz: TimeZone>(fetched: &DateTime<Tz>, now: &DateTime<Tz>) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    if fetched.date_naive() == now.date_naive() {
+        fetched.format("%H:%M").to_string()
+    } else {
+        fetched.format("%Y-%m-%d %H:%M").to_string()
+    }
+}
+
+fn weather_tooltip(location: &Location, units: &UnitSettings, weather: &Observation) -> String {
+    format!(
+        "{}: {} - {}",
+        location.name,
+        weather.temperature.convert(units.temperature),
+        weather.condition.description()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::fetched_time;
+
+    #[test]
+    fn show_date_of_older_weather() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 2, 9, 30, 0).unwrap();
+        let today = Utc.with_ymd_and_hms(2024, 5, 2, 8, 15, 0).unwrap();
+        let yesterday = Utc.with_ymd_and_hms(2024, 5, 1, 23, 45, 0).unwrap();
+        assert_eq!(fetched_time(&today, &now), "08:15");
+        assert_eq!(fetched_time(&yesterday, &now), "2024-05-01 23:45");
+    }
+}
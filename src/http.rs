@@ -0,0 +1,152 @@
+use std::{fs, time::Duration};
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use reqwest::{
+    header::{HeaderMap, HeaderName, RETRY_AFTER},
+    Certificate, Client, Proxy, Response, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    PROGRAM_NAME,
+};
+
+/// Connection settings used for all HTTP requests
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct HttpSettings {
+    /// Seconds to wait for a connection
+    pub connect_timeout: u64,
+    /// Seconds to wait for data of a response
+    pub read_timeout: u64,
+    /// Proxy like `http://proxy:8080` or `socks5://proxy:1080`, empty for none
+    pub proxy: String,
+    /// Path of an additional trusted PEM root certificate, empty for none
+    pub ca_certificate: String,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout: 10,
+            read_timeout: 30,
+            proxy: String::new(),
+            ca_certificate: String::new(),
+        }
+    }
+}
+
+/// User-Agent identifying this app, required by some APIs
+pub(crate) fn user_agent() -> String {
+    format!(
+        "{}/{} github.com/ponchofiesta/tray-weather",
+        PROGRAM_NAME.replace(' ', ""),
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// Build the HTTP client shared by all weather providers
+pub(crate) fn create_client(settings: &HttpSettings) -> Result<Client> {
+    let mut builder = Client::builder()
+        .user_agent(user_agent())
+        .connect_timeout(Duration::from_secs(settings.connect_timeout))
+        .read_timeout(Duration::from_secs(settings.read_timeout));
+    if !settings.proxy.is_empty() {
+        builder = builder.proxy(Proxy::all(&settings.proxy)?);
+    }
+    if !settings.ca_certificate.is_empty() {
+        let pem = fs::read(&settings.ca_certificate)?;
+        builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Build the HTTP client, falling back to the default settings if `settings` are invalid
+pub(crate) fn create_client_or_default(settings: &HttpSettings) -> Client {
+    create_client(settings).unwrap_or_else(|err| {
+        warn!("Invalid HTTP settings, using the defaults: {err}");
+        create_client(&HttpSettings::default()).unwrap_or_default()
+    })
+}
+
+/// Parse a header containing an HTTP date
+pub(crate) fn header_date(headers: &HeaderMap, name: HeaderName) -> Option<DateTime<Utc>> {
+    let value = headers.get(name)?.to_str().ok()?;
+    DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Wait time requested by a `Retry-After` header, given in seconds or as HTTP date
+pub(crate) fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    match value.trim().parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => (header_date(headers, RETRY_AFTER)? - now).to_std().ok(),
+    }
+}
+
+/// Turn error status codes into errors, keeping `Retry-After` of rate limited requests
+pub(crate) fn check_status(response: Response) -> Result<Response> {
+    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        return Err(Error::RateLimited(retry_after(
+            response.headers(),
+            Utc::now(),
+        )));
+    }
+    Ok(response.error_for_status()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::DateTime;
+    use reqwest::header::{HeaderMap, HeaderValue, EXPIRES, RETRY_AFTER};
+
+    use super::{create_client, header_date, retry_after, HttpSettings};
+
+    #[test]
+    fn build_client() {
+        assert!(create_client(&HttpSettings::default()).is_ok());
+        let settings = HttpSettings {
+            proxy: "socks5://localhost:1080".into(),
+            ..Default::default()
+        };
+        assert!(create_client(&settings).is_ok());
+        let settings = HttpSettings {
+            ca_certificate: "does-not-exist.pem".into(),
+            ..Default::default()
+        };
+        assert!(create_client(&settings).is_err());
+    }
+
+    #[test]
+    fn parse_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2024-10-21T17:33:01Z")
+            .unwrap()
+            .to_utc();
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers, now), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(120)));
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Mon, 21 Oct 2024 17:34:01 GMT"),
+        );
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn parse_expires_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            EXPIRES,
+            HeaderValue::from_static("Mon, 21 Oct 2024 17:33:01 GMT"),
+        );
+        let expires = header_date(&headers, EXPIRES).unwrap();
+        assert_eq!(expires.to_rfc3339(), "2024-10-21T17:33:01+00:00");
+    }
+}
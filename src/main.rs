@@ -1,9 +1,14 @@
 #![windows_subsystem = "windows"]
 
 mod app;
+mod cache;
 mod error;
 mod gui;
+mod http;
+mod provider;
+mod retry;
 mod settings;
+mod units;
 mod weather;
 
 use std::{
@@ -11,16 +16,19 @@ use std::{
     time::Duration,
 };
 
-use app::{TaskGuard, WeatherApp};
+use app::{FetchedForecast, TaskGuard, WeatherApp};
 use async_winit::{event_loop::EventLoop, ThreadUnsafe};
 use error::{Error, Result};
-use gui::{forecast_window::show_forecast_window, settings_window::show_settings_window};
-use log::{debug, trace};
+use gui::{
+    color_scheme::ColorScheme, forecast_window::show_forecast_window,
+    settings_window::show_settings_window,
+};
+use log::{debug, trace, warn};
 use rust_i18n::t;
 use settings::Settings;
 use tray_icon::{
-    menu::{Menu, MenuEvent, MenuItem},
-    MouseButton, MouseButtonState, TrayIconEvent,
+    menu::{Menu, MenuEvent, MenuItem, Submenu},
+    MouseButton, MouseButtonState, TrayIconEvent, TrayIconId,
 };
 
 pub const PROGRAM_NAME: &str = "Tray Weather";
@@ -35,13 +43,17 @@ fn localization() {
 
 enum Message {
     Update,
+    SelectLocation(usize),
     ShowSettings,
-    ShowForecast,
+    ShowForecast(TrayIconId),
+    ColorSchemeChanged(Option<ColorScheme>),
+    Fetched(Box<FetchedForecast>),
     Quit,
 }
 
 enum MenuId {
     Update,
+    Location(usize),
     Settings,
     Quit,
 }
@@ -49,11 +61,12 @@ enum MenuId {
 impl ToString for MenuId {
     fn to_string(&self) -> String {
         use MenuId::*;
-        String::from(match self {
-            Update => "update",
-            Settings => "settings",
-            Quit => "quit",
-        })
+        match self {
+            Update => String::from("update"),
+            Location(index) => format!("location_{index}"),
+            Settings => String::from("settings"),
+            Quit => String::from("quit"),
+        }
     }
 }
 
@@ -76,15 +89,16 @@ async fn main() -> Result<()> {
 
     // Build tray menu
     let item_update = MenuItem::with_id(MenuId::Update, t!("update"), true, None);
+    let item_locations = Submenu::new(t!("locations"), true);
     let item_config = MenuItem::with_id(MenuId::Settings, t!("settings"), true, None);
     let item_exit = MenuItem::with_id(MenuId::Quit, t!("quit"), true, None);
-    let menu = Menu::with_items(&[&item_update, &item_config, &item_exit])?;
+    let menu = Menu::with_items(&[&item_update, &item_locations, &item_config, &item_exit])?;
 
-    let mut app = WeatherApp::new(settings, menu)?;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+    let mut app = WeatherApp::new(settings, menu, item_locations, tx.clone())?;
 
     let event_loop: EventLoop<ThreadUnsafe> = EventLoop::new();
     let window_target = event_loop.window_target().clone();
-    let (tx, mut rx) = tokio::sync::mpsc::channel(32);
     let mut task_guard = TaskGuard::new();
 
     let sleep_update_interval = update_interval.clone();
@@ -116,10 +130,11 @@ async fn main() -> Result<()> {
                 println!("tray event");
                 let msg = match event {
                     TrayIconEvent::Click {
+                        id,
                         button: MouseButton::Left,
                         button_state: MouseButtonState::Up,
                         ..
-                    } => Message::ShowForecast,
+                    } => Message::ShowForecast(id),
                     _ => continue,
                 };
                 let _ = tray_tx.send(msg).await;
@@ -132,8 +147,15 @@ async fn main() -> Result<()> {
     tokio::spawn(async move {
         loop {
             if let Ok(event) = MenuEvent::receiver().recv() {
+                let location = event
+                    .id()
+                    .0
+                    .strip_prefix("location_")
+                    .and_then(|index| index.parse().ok());
                 let msg = if event.id() == MenuId::Update.to_string() {
                     Message::Update
+                } else if let Some(index) = location {
+                    Message::SelectLocation(index)
                 } else if event.id() == MenuId::Settings.to_string() {
                     Message::ShowSettings
                 } else if event.id() == MenuId::Quit.to_string() {
@@ -146,8 +168,14 @@ async fn main() -> Result<()> {
         }
     });
 
+    // Proxy for color scheme changes
+    let color_scheme_tx = tx.clone();
+    ColorScheme::watch(move |color_scheme| {
+        let _ = color_scheme_tx.blocking_send(Message::ColorSchemeChanged(color_scheme));
+    });
+
     // Initial weather update
-    app.update_weather().await?;
+    app.update_weather()?;
 
     let setting_update_interval = update_interval.clone();
 
@@ -157,16 +185,50 @@ async fn main() -> Result<()> {
             trace!("eventloop iteration starts");
             if let Some(msg) = rx.recv().await {
                 match msg {
-                    Message::Update => app.update_weather().await.unwrap(),
+                    Message::Update => app.update_weather().unwrap(),
+                    Message::SelectLocation(index) => {
+                        if let Err(err) = app.select_location(index) {
+                            warn!("Could not switch location: {err}");
+                        }
+                    }
                     Message::ShowSettings => {
                         if let Some(new_settings) = show_settings_window(&app.settings) {
                             app.settings.update(&new_settings);
                             app.settings.save().expect("Could not save settings.");
                             *setting_update_interval.lock().unwrap() = app.settings.update_interval;
-                            app.update_settings().await.unwrap();
+                            if let Err(err) = app.update_settings() {
+                                warn!("Could not apply settings: {err}");
+                            }
+                        }
+                    }
+                    Message::ShowForecast(id) => {
+                        let tray = app.tray_by_id(&id.0);
+                        let (forecast, error) = (tray.forecast.clone(), tray.error.clone());
+                        let pending = match *forecast.lock().unwrap() {
+                            Some(_) => None,
+                            None => app.fetch_tray(&id.0),
+                        };
+                        let shown = show_forecast_window(
+                            forecast,
+                            error,
+                            pending,
+                            &app.settings,
+                            app.provider.clone(),
+                        );
+                        if let Err(err) = shown {
+                            warn!("Could not show forecast: {err}");
+                        }
+                    }
+                    Message::ColorSchemeChanged(color_scheme) => {
+                        if let Err(err) = app.set_system_color_scheme(color_scheme) {
+                            warn!("Could not redraw weather: {err}");
+                        }
+                    }
+                    Message::Fetched(fetched) => {
+                        if let Err(err) = app.show_fetched(*fetched) {
+                            warn!("Could not show weather: {err}");
                         }
                     }
-                    Message::ShowForecast => show_forecast_window(&app.settings).unwrap(),
                     Message::Quit => window_target.exit().await,
                 }
             }
@@ -0,0 +1,542 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, Offset, Timelike, Utc};
+use log::{debug, warn};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+
+use crate::{
+    error::{Error, Result},
+    http::check_status,
+    units::{Precipitation, PrecipitationUnit, Speed, SpeedUnit, Temperature, TemperatureUnit},
+    weather::{
+        Alert, DailyPoint, Daylight, Forecast, HourlyPoint, Intensity, Location, Observation,
+        WeatherCondition,
+    },
+};
+
+use super::{open_meteo::OpenMeteo, WeatherProvider};
+
+const WEATHER_URL: &str = "https://api.brightsky.dev/weather";
+const CURRENT_WEATHER_URL: &str = "https://api.brightsky.dev/current_weather";
+const ALERTS_URL: &str = "https://api.brightsky.dev/alerts";
+
+// Units of the default "dwd" unit system
+const TEMPERATURE_UNIT: TemperatureUnit = TemperatureUnit::Celsius;
+const SPEED_UNIT: SpeedUnit = SpeedUnit::KilometresPerHour;
+const PRECIPITATION_UNIT: PrecipitationUnit = PrecipitationUnit::Millimetres;
+
+const FORECAST_HOURS: usize = 12;
+const FORECAST_DAYS: usize = 7;
+
+/// Bright Sky `icon` field
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum WeatherIcon {
+    ClearDay,
+    ClearNight,
+    PartlyCloudyDay,
+    PartlyCloudyNight,
+    Cloudy,
+    Fog,
+    Wind,
+    Rain,
+    Sleet,
+    Snow,
+    Hail,
+    Thunderstorm,
+    #[serde(other)]
+    Unknown,
+}
+
+/// Bright Sky `condition` field
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Condition {
+    Dry,
+    Fog,
+    Rain,
+    Sleet,
+    Snow,
+    Hail,
+    Thunderstorm,
+    #[serde(other)]
+    Unknown,
+}
+
+/// Representation for Bright Sky `/weather` response object
+#[derive(Debug, Deserialize)]
+pub(crate) struct WeatherRecords {
+    pub weather: Vec<WeatherRecord>,
+}
+
+/// Hourly record of the `/weather` endpoint
+#[derive(Debug, Deserialize)]
+pub(crate) struct WeatherRecord {
+    pub timestamp: DateTime<FixedOffset>,
+    pub precipitation: Option<f32>,
+    pub temperature: Option<f32>,
+    pub wind_direction: Option<u16>,
+    pub wind_speed: Option<f32>,
+    pub wind_gust_speed: Option<f32>,
+    pub cloud_cover: Option<f32>,
+    pub condition: Option<Condition>,
+    pub icon: Option<WeatherIcon>,
+}
+
+/// Representation for Bright Sky `/current_weather` response object
+#[derive(Debug, Deserialize)]
+pub(crate) struct CurrentWeatherRecord {
+    pub weather: CurrentWeather,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CurrentWeather {
+    pub timestamp: DateTime<FixedOffset>,
+    pub precipitation_60: Option<f32>,
+    pub temperature: Option<f32>,
+    pub wind_direction_10: Option<u16>,
+    pub wind_speed_10: Option<f32>,
+    pub wind_gust_speed_60: Option<f32>,
+    pub cloud_cover: Option<f32>,
+    pub condition: Option<Condition>,
+    pub icon: Option<WeatherIcon>,
+}
+
+/// Representation for Bright Sky `/alerts` response object
+#[derive(Debug, Deserialize)]
+pub(crate) struct Alerts {
+    pub alerts: Vec<DwdAlert>,
+}
+
+/// Warning of the DWD as returned by `/alerts`
+#[derive(Debug, Deserialize)]
+pub(crate) struct DwdAlert {
+    pub onset: Option<DateTime<FixedOffset>>,
+    pub expires: Option<DateTime<FixedOffset>>,
+    pub event_en: Option<String>,
+    pub event_de: Option<String>,
+}
+
+impl From<&DwdAlert> for Alert {
+    fn from(value: &DwdAlert) -> Self {
+        Alert {
+            event: value
+                .event_en
+                .clone()
+                .or_else(|| value.event_de.clone())
+                .unwrap_or_default(),
+            onset: value.onset.map(|onset| onset.to_utc()),
+            ends: value.expires.map(|expires| expires.to_utc()),
+        }
+    }
+}
+
+/// Intensity of hourly precipitation following the DWD thresholds for rain
+fn precipitation_intensity(precipitation: f32) -> Intensity {
+    if precipitation < 2.5 {
+        Intensity::Light
+    } else if precipitation < 10.0 {
+        Intensity::Moderate
+    } else {
+        Intensity::Heavy
+    }
+}
+
+/// Sky condition of dry weather from cloud cover in percent
+fn sky_condition(cloud_cover: Option<f32>) -> WeatherCondition {
+    match cloud_cover {
+        Some(cover) if cover < 12.5 => WeatherCondition::ClearSky,
+        Some(cover) if cover < 37.5 => WeatherCondition::MainlyClear,
+        Some(cover) if cover < 75.0 => WeatherCondition::PartlyCloudy,
+        Some(_) => WeatherCondition::Overcast,
+        None => WeatherCondition::Unknown,
+    }
+}
+
+/// Combine the `icon` and `condition` fields of a record into a [WeatherCondition]
+pub(crate) fn to_condition(
+    icon: Option<WeatherIcon>,
+    condition: Option<Condition>,
+    cloud_cover: Option<f32>,
+    precipitation: Option<f32>,
+) -> WeatherCondition {
+    let intensity = precipitation_intensity(precipitation.unwrap_or_default());
+    match (icon, condition) {
+        (Some(WeatherIcon::Thunderstorm), _) | (_, Some(Condition::Thunderstorm)) => {
+            WeatherCondition::Thunderstorm
+        }
+        // Hail in Germany almost exclusively comes with thunderstorms
+        (Some(WeatherIcon::Hail), _) | (_, Some(Condition::Hail)) => {
+            WeatherCondition::ThunderstormWithHail(intensity)
+        }
+        (Some(WeatherIcon::Snow), _) | (_, Some(Condition::Snow)) => {
+            WeatherCondition::Snow(intensity)
+        }
+        (Some(WeatherIcon::Sleet), _) | (_, Some(Condition::Sleet)) => {
+            WeatherCondition::Sleet(intensity)
+        }
+        (Some(WeatherIcon::Rain), _) | (_, Some(Condition::Rain)) => {
+            WeatherCondition::Rain(intensity)
+        }
+        (Some(WeatherIcon::Fog), _) | (_, Some(Condition::Fog)) => WeatherCondition::Fog,
+        (Some(WeatherIcon::ClearDay | WeatherIcon::ClearNight), _) => WeatherCondition::ClearSky,
+        (Some(WeatherIcon::PartlyCloudyDay | WeatherIcon::PartlyCloudyNight), _) => {
+            WeatherCondition::PartlyCloudy
+        }
+        (Some(WeatherIcon::Cloudy), _) => WeatherCondition::Overcast,
+        _ => sky_condition(cloud_cover),
+    }
+}
+
+/// Night icons of Bright Sky, all others are shown as day
+fn to_daylight(icon: Option<WeatherIcon>) -> Daylight {
+    match icon {
+        Some(WeatherIcon::ClearNight | WeatherIcon::PartlyCloudyNight) => Daylight::Night,
+        _ => Daylight::Day,
+    }
+}
+
+impl WeatherRecord {
+    fn condition(&self) -> WeatherCondition {
+        to_condition(
+            self.icon,
+            self.condition,
+            self.cloud_cover,
+            self.precipitation,
+        )
+    }
+
+    /// Time in the time zone the records were requested in
+    fn time(&self) -> NaiveDateTime {
+        self.timestamp.naive_local()
+    }
+
+    /// Records without temperature are skipped
+    fn to_hourly_point(&self) -> Option<HourlyPoint> {
+        Some(HourlyPoint {
+            time: self.time(),
+            condition: self.condition(),
+            daylight: to_daylight(self.icon),
+            temperature: Temperature::new(self.temperature?, TEMPERATURE_UNIT),
+            precipitation: self
+                .precipitation
+                .map(|value| Precipitation::new(value, PRECIPITATION_UNIT)),
+            wind_speed: Speed::new(self.wind_speed.unwrap_or_default(), SPEED_UNIT),
+            wind_direction: self.wind_direction.unwrap_or_default(),
+            wind_gusts: self
+                .wind_gust_speed
+                .map(|value| Speed::new(value, SPEED_UNIT)),
+        })
+    }
+}
+
+impl TryFrom<CurrentWeather> for Observation {
+    type Error = Error;
+
+    fn try_from(value: CurrentWeather) -> Result<Self> {
+        Ok(Observation {
+            time: value.timestamp.naive_local(),
+            condition: to_condition(
+                value.icon,
+                value.condition,
+                value.cloud_cover,
+                value.precipitation_60,
+            ),
+            daylight: to_daylight(value.icon),
+            temperature: Temperature::new(
+                value
+                    .temperature
+                    .ok_or_else(|| Error::other("No temperature received."))?,
+                TEMPERATURE_UNIT,
+            ),
+            precipitation: value
+                .precipitation_60
+                .map(|value| Precipitation::new(value, PRECIPITATION_UNIT)),
+            wind_speed: Speed::new(value.wind_speed_10.unwrap_or_default(), SPEED_UNIT),
+            wind_direction: value.wind_direction_10.unwrap_or_default(),
+            wind_gusts: value
+                .wind_gust_speed_60
+                .map(|value| Speed::new(value, SPEED_UNIT)),
+        })
+    }
+}
+
+/// Aggregate the hourly records of one day
+fn daily_point(date: NaiveDate, records: &[&WeatherRecord]) -> Option<DailyPoint> {
+    let temperatures = records.iter().filter_map(|record| record.temperature);
+    let temperature_max = temperatures.clone().reduce(f32::max)?;
+    let temperature_min = temperatures.reduce(f32::min)?;
+    let precipitation_sum = records
+        .iter()
+        .filter_map(|record| record.precipitation)
+        .sum::<f32>();
+    let windiest = records
+        .iter()
+        .filter(|record| record.wind_speed.is_some())
+        .max_by(|a, b| {
+            a.wind_speed
+                .unwrap_or_default()
+                .total_cmp(&b.wind_speed.unwrap_or_default())
+        });
+    let wind_gusts_max = records
+        .iter()
+        .filter_map(|record| record.wind_gust_speed)
+        .reduce(f32::max);
+    // Show the wettest hour on rainy days, the midday sky otherwise
+    let condition = if precipitation_sum > 0.0 {
+        records.iter().max_by(|a, b| {
+            a.precipitation
+                .unwrap_or_default()
+                .total_cmp(&b.precipitation.unwrap_or_default())
+        })
+    } else {
+        records
+            .iter()
+            .find(|record| record.time().hour() >= 12)
+            .or(records.first())
+    }
+    .map_or(WeatherCondition::Unknown, |record| record.condition());
+
+    Some(DailyPoint {
+        date,
+        condition,
+        temperature_max: Temperature::new(temperature_max, TEMPERATURE_UNIT),
+        temperature_min: Temperature::new(temperature_min, TEMPERATURE_UNIT),
+        precipitation_sum: Some(Precipitation::new(precipitation_sum, PRECIPITATION_UNIT)),
+        wind_speed_max: Speed::new(
+            windiest.and_then(|r| r.wind_speed).unwrap_or_default(),
+            SPEED_UNIT,
+        ),
+        wind_gusts_max: wind_gusts_max.map(|value| Speed::new(value, SPEED_UNIT)),
+        wind_direction_dominant: windiest.and_then(|r| r.wind_direction).unwrap_or_default(),
+    })
+}
+
+impl WeatherRecords {
+    /// Build hourly and daily forecast starting at `now`, in the time zone of the records
+    fn to_forecast(&self, now: DateTime<Utc>) -> Forecast {
+        let offset = self
+            .weather
+            .iter()
+            .rev()
+            .find(|record| record.timestamp <= now)
+            .or(self.weather.first())
+            .map_or(Utc.fix(), |record| *record.timestamp.offset());
+        let now = now.with_timezone(&offset).naive_local();
+        let current_hour = now.date().and_hms_opt(now.hour(), 0, 0).unwrap_or(now);
+
+        let hourly = self
+            .weather
+            .iter()
+            .filter(|record| record.time() >= current_hour)
+            .filter_map(WeatherRecord::to_hourly_point)
+            .take(FORECAST_HOURS)
+            .collect();
+
+        let mut days: BTreeMap<NaiveDate, Vec<&WeatherRecord>> = BTreeMap::new();
+        for record in &self.weather {
+            days.entry(record.time().date()).or_default().push(record);
+        }
+        let daily = days
+            .range(now.date()..)
+            .filter_map(|(date, records)| daily_point(*date, records))
+            .take(FORECAST_DAYS)
+            .collect();
+
+        Forecast {
+            current: None,
+            hourly,
+            daily,
+            utc_offset_seconds: offset.local_minus_utc(),
+            alerts: vec![],
+        }
+    }
+}
+
+/// [WeatherProvider] for the Bright Sky API serving DWD data
+///
+/// The API has no location search, so Open Meteo is used for that.
+pub(crate) struct BrightSky {
+    client: Client,
+    geocoder: OpenMeteo,
+}
+
+impl BrightSky {
+    pub fn new(client: Client, geocoder: OpenMeteo) -> Self {
+        BrightSky { client, geocoder }
+    }
+
+    async fn get_current_weather(&self, location: &Location) -> Result<Observation> {
+        debug!("get_weather({location:?})");
+        let params = [
+            ("lat", location.latitude.to_string()),
+            ("lon", location.longitude.to_string()),
+            ("tz", location.timezone.clone()),
+        ];
+        let url = Url::parse_with_params(CURRENT_WEATHER_URL, &params).map_err(Error::other)?;
+        let response = self.client.get(url).send().await?;
+        let response = check_status(response)?
+            .json::<CurrentWeatherRecord>()
+            .await?;
+        response.weather.try_into()
+    }
+
+    async fn get_alerts(&self, location: &Location) -> Result<Vec<Alert>> {
+        let params = [
+            ("lat", location.latitude.to_string()),
+            ("lon", location.longitude.to_string()),
+        ];
+        let url = Url::parse_with_params(ALERTS_URL, &params).map_err(Error::other)?;
+        let response = self.client.get(url).send().await?;
+        let response = check_status(response)?.json::<Alerts>().await?;
+        Ok(response.alerts.iter().map(Alert::from).collect())
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for BrightSky {
+    async fn forecast(&self, location: &Location) -> Result<Forecast> {
+        debug!("get_forecast({location:?})");
+        let now = Utc::now();
+        // Dates are read in the time zone of the location, which may be a day off the UTC date
+        let date = now.date_naive() - Duration::days(1);
+        let last_date = now.date_naive() + Duration::days(FORECAST_DAYS as i64 + 1);
+        let params = [
+            ("lat", location.latitude.to_string()),
+            ("lon", location.longitude.to_string()),
+            ("date", date.to_string()),
+            ("last_date", last_date.to_string()),
+            ("tz", location.timezone.clone()),
+        ];
+        let url = Url::parse_with_params(WEATHER_URL, &params).map_err(Error::other)?;
+        let response = self.client.get(url).send().await?;
+        let response = check_status(response)?.json::<WeatherRecords>().await?;
+        let mut forecast = response.to_forecast(now);
+        // The forecast is still useful without current weather or alerts
+        forecast.current = self
+            .get_current_weather(location)
+            .await
+            .inspect_err(|err| warn!("Could not get current weather: {err}"))
+            .ok();
+        forecast.alerts = self
+            .get_alerts(location)
+            .await
+            .inspect_err(|err| warn!("Could not get alerts: {err}"))
+            .unwrap_or_default();
+        Ok(forecast)
+    }
+
+    async fn search_location(&self, name: &str, lang: &str) -> Result<Vec<Location>> {
+        self.geocoder.search_location(name, lang).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::weather::{Alert, Intensity, Observation, WeatherCondition};
+
+    use super::{
+        to_condition, Alerts, Condition, CurrentWeatherRecord, WeatherIcon, WeatherRecords,
+    };
+
+    #[test]
+    fn decode_weather() {
+        let result: Result<WeatherRecords, serde_json::Error> =
+            serde_json::from_str(include_str!("../../tests/data/brightsky_weather.json"));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn convert_weather() {
+        let response: WeatherRecords =
+            serde_json::from_str(include_str!("../../tests/data/brightsky_weather.json")).unwrap();
+        let now = DateTime::parse_from_rfc3339("2024-10-21T17:20:00Z")
+            .unwrap()
+            .to_utc();
+        let forecast = response.to_forecast(now);
+        assert_eq!(forecast.hourly.len(), 12);
+        assert_eq!(forecast.hourly[0].time.to_string(), "2024-10-21 17:00:00");
+        assert_eq!(forecast.daily.len(), 7);
+        assert_eq!(forecast.daily[0].date.to_string(), "2024-10-21");
+    }
+
+    #[test]
+    fn convert_weather_in_local_time() {
+        let record = |timestamp: &str| {
+            format!(r#"{{"timestamp": "{timestamp}", "temperature": 10.0, "icon": "cloudy"}}"#)
+        };
+        let json = format!(
+            r#"{{"weather": [{}, {}, {}]}}"#,
+            record("2024-10-21T22:00:00+02:00"),
+            record("2024-10-21T23:00:00+02:00"),
+            record("2024-10-22T00:00:00+02:00"),
+        );
+        let response: WeatherRecords = serde_json::from_str(&json).unwrap();
+        let now = DateTime::parse_from_rfc3339("2024-10-21T21:30:00Z")
+            .unwrap()
+            .to_utc();
+        let forecast = response.to_forecast(now);
+        assert_eq!(forecast.utc_offset_seconds, 7200);
+        assert_eq!(forecast.hourly[0].time.to_string(), "2024-10-21 23:00:00");
+        let dates: Vec<_> = forecast
+            .daily
+            .iter()
+            .map(|day| day.date.to_string())
+            .collect();
+        assert_eq!(dates, ["2024-10-21", "2024-10-22"]);
+    }
+
+    #[test]
+    fn convert_current_weather() {
+        let response: CurrentWeatherRecord = serde_json::from_str(include_str!(
+            "../../tests/data/brightsky_current_weather.json"
+        ))
+        .unwrap();
+        let observation = Observation::try_from(response.weather).unwrap();
+        assert_eq!(observation.condition, WeatherCondition::Overcast);
+        assert_eq!(observation.temperature.value, 14.0);
+    }
+
+    #[test]
+    fn convert_alerts() {
+        let response: Alerts =
+            serde_json::from_str(include_str!("../../tests/data/brightsky_alerts.json")).unwrap();
+        let alert = Alert::from(&response.alerts[0]);
+        assert_eq!(alert.event, "wind gusts");
+        let time = DateTime::parse_from_rfc3339("2024-10-21T17:20:00Z")
+            .unwrap()
+            .to_utc();
+        assert!(alert.is_active(time));
+        assert!(!Alert::from(&response.alerts[1]).is_active(time));
+    }
+
+    #[test]
+    fn map_conditions() {
+        assert_eq!(
+            to_condition(
+                Some(WeatherIcon::Rain),
+                Some(Condition::Rain),
+                None,
+                Some(3.1)
+            ),
+            WeatherCondition::Rain(Intensity::Moderate)
+        );
+        assert_eq!(
+            to_condition(
+                Some(WeatherIcon::Wind),
+                Some(Condition::Dry),
+                Some(20.0),
+                None
+            ),
+            WeatherCondition::MainlyClear
+        );
+        assert_eq!(
+            to_condition(None, Some(Condition::Thunderstorm), None, None),
+            WeatherCondition::Thunderstorm
+        );
+    }
+}
@@ -0,0 +1,482 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Timelike, Utc};
+use log::debug;
+use reqwest::{
+    header::{EXPIRES, IF_MODIFIED_SINCE, LAST_MODIFIED},
+    Client, StatusCode,
+};
+use serde::Deserialize;
+
+use crate::{
+    error::{Error, Result},
+    http::{check_status, header_date},
+    units::{Precipitation, PrecipitationUnit, Speed, SpeedUnit, Temperature, TemperatureUnit},
+    weather::{
+        DailyPoint, Daylight, Forecast, HourlyPoint, Intensity, Location, Observation,
+        WeatherCondition,
+    },
+};
+
+use super::{open_meteo::OpenMeteo, WeatherProvider};
+
+const FORECAST_URL: &str = "https://api.met.no/weatherapi/locationforecast/2.0/compact";
+
+// Units of the compact format
+const TEMPERATURE_UNIT: TemperatureUnit = TemperatureUnit::Celsius;
+const SPEED_UNIT: SpeedUnit = SpeedUnit::MetresPerSecond;
+const PRECIPITATION_UNIT: PrecipitationUnit = PrecipitationUnit::Millimetres;
+
+const FORECAST_HOURS: usize = 12;
+const FORECAST_DAYS: usize = 7;
+
+/// Representation for MET Norway Locationforecast compact response object
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct LocationForecast {
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct Properties {
+    pub timeseries: Vec<TimeStep>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct TimeStep {
+    pub time: DateTime<Utc>,
+    pub data: TimeStepData,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct TimeStepData {
+    pub instant: Instant,
+    pub next_1_hours: Option<Period>,
+    pub next_6_hours: Option<Period>,
+    pub next_12_hours: Option<Period>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct Instant {
+    pub details: InstantDetails,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct InstantDetails {
+    pub air_temperature: f32,
+    pub wind_from_direction: f32,
+    pub wind_speed: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct Period {
+    pub summary: Summary,
+    #[serde(default)]
+    pub details: PeriodDetails,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct Summary {
+    pub symbol_code: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct PeriodDetails {
+    pub precipitation_amount: Option<f32>,
+}
+
+/// Map a met.no symbol code like `lightrainshowers_day` to a [WeatherCondition]
+pub(crate) fn condition_from_symbol(symbol_code: &str) -> WeatherCondition {
+    use Intensity::*;
+    use WeatherCondition::*;
+
+    // Strip variants like _day, _night and _polartwilight
+    let name = symbol_code.split('_').next().unwrap_or_default();
+    let (intensity, name) = if let Some(name) = name.strip_prefix("light") {
+        // The API contains some legacy typos like "lightssleetshowersandthunder"
+        let name = name
+            .strip_prefix('s')
+            .filter(|n| n.starts_with('s'))
+            .unwrap_or(name);
+        (Light, name)
+    } else if let Some(name) = name.strip_prefix("heavy") {
+        (Heavy, name)
+    } else {
+        (Moderate, name)
+    };
+    let (name, thunder) = match name.strip_suffix("andthunder") {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+
+    match (name, thunder) {
+        ("clearsky", _) => ClearSky,
+        ("fair", _) => MainlyClear,
+        ("partlycloudy", _) => PartlyCloudy,
+        ("cloudy", _) => Overcast,
+        ("fog", _) => Fog,
+        ("rain" | "rainshowers", true) => RainAndThunder(intensity),
+        ("sleet" | "sleetshowers", true) => SleetAndThunder(intensity),
+        ("snow" | "snowshowers", true) => SnowAndThunder(intensity),
+        ("rain", false) => Rain(intensity),
+        ("rainshowers", false) => RainShowers(intensity),
+        ("sleet" | "sleetshowers", false) => Sleet(intensity),
+        ("snow", false) => Snow(intensity),
+        ("snowshowers", false) => SnowShowers(intensity),
+        _ => Unknown,
+    }
+}
+
+/// Read the day, night or polar twilight variant of a met.no symbol code
+pub(crate) fn daylight_from_symbol(symbol_code: &str) -> Daylight {
+    match symbol_code.split_once('_') {
+        Some((_, "night")) => Daylight::Night,
+        Some((_, "polartwilight")) => Daylight::PolarTwilight,
+        _ => Daylight::Day,
+    }
+}
+
+impl TimeStepData {
+    /// Shortest forecast period starting at this time step
+    fn next_period(&self) -> Option<&Period> {
+        self.next_1_hours
+            .as_ref()
+            .or(self.next_6_hours.as_ref())
+            .or(self.next_12_hours.as_ref())
+    }
+
+    fn condition(&self) -> WeatherCondition {
+        self.next_period()
+            .map(|period| condition_from_symbol(&period.summary.symbol_code))
+            .unwrap_or(WeatherCondition::Unknown)
+    }
+
+    fn daylight(&self) -> Daylight {
+        self.next_period()
+            .map(|period| daylight_from_symbol(&period.summary.symbol_code))
+            .unwrap_or_default()
+    }
+}
+
+impl From<&TimeStep> for Observation {
+    fn from(value: &TimeStep) -> Self {
+        let details = &value.data.instant.details;
+        Observation {
+            time: value.time.naive_utc(),
+            condition: value.data.condition(),
+            daylight: value.data.daylight(),
+            temperature: Temperature::new(details.air_temperature, TEMPERATURE_UNIT),
+            precipitation: value
+                .data
+                .next_1_hours
+                .as_ref()
+                .and_then(|period| period.details.precipitation_amount)
+                .map(|amount| Precipitation::new(amount, PRECIPITATION_UNIT)),
+            wind_speed: Speed::new(details.wind_speed, SPEED_UNIT),
+            wind_direction: details.wind_from_direction.round() as u16,
+            wind_gusts: None,
+        }
+    }
+}
+
+impl TimeStep {
+    fn local_time(&self, offset: &FixedOffset) -> NaiveDateTime {
+        self.time.with_timezone(offset).naive_local()
+    }
+}
+
+/// Aggregate hourly and 6-hourly time steps of one day
+fn daily_point(date: NaiveDate, steps: &[&TimeStep], offset: &FixedOffset) -> DailyPoint {
+    let temperatures = steps
+        .iter()
+        .map(|step| step.data.instant.details.air_temperature);
+    let temperature_max = temperatures.clone().fold(f32::MIN, f32::max);
+    let temperature_min = temperatures.fold(f32::MAX, f32::min);
+    let precipitation_sum = steps
+        .iter()
+        .filter_map(|step| {
+            // Steps with a next_1_hours period are hourly, later ones are 6-hourly
+            match step.data.next_1_hours {
+                Some(ref period) => period.details.precipitation_amount,
+                None => step
+                    .data
+                    .next_6_hours
+                    .as_ref()
+                    .and_then(|period| period.details.precipitation_amount),
+            }
+        })
+        .sum();
+    let windiest = steps
+        .iter()
+        .map(|step| &step.data.instant.details)
+        .max_by(|a, b| a.wind_speed.total_cmp(&b.wind_speed));
+    // Prefer the symbol describing the day time
+    let condition = steps
+        .iter()
+        .find(|step| step.local_time(offset).hour() >= 6 && step.data.next_6_hours.is_some())
+        .or(steps.first())
+        .map(|step| match step.data.next_6_hours {
+            Some(ref period) => condition_from_symbol(&period.summary.symbol_code),
+            None => step.data.condition(),
+        })
+        .unwrap_or(WeatherCondition::Unknown);
+
+    DailyPoint {
+        date,
+        condition,
+        temperature_max: Temperature::new(temperature_max, TEMPERATURE_UNIT),
+        temperature_min: Temperature::new(temperature_min, TEMPERATURE_UNIT),
+        precipitation_sum: Some(Precipitation::new(precipitation_sum, PRECIPITATION_UNIT)),
+        wind_speed_max: Speed::new(windiest.map_or(0.0, |d| d.wind_speed), SPEED_UNIT),
+        wind_gusts_max: None,
+        wind_direction_dominant: windiest.map_or(0, |d| d.wind_from_direction.round() as u16),
+    }
+}
+
+impl LocationForecast {
+    /// Convert the UTC time steps into a [Forecast] in the time zone at `offset`
+    fn to_forecast(&self, offset: FixedOffset) -> Forecast {
+        let timeseries = &self.properties.timeseries;
+
+        let hourly = timeseries
+            .iter()
+            .filter_map(|step| {
+                let period = step.data.next_1_hours.as_ref()?;
+                let details = &step.data.instant.details;
+                Some(HourlyPoint {
+                    time: step.local_time(&offset),
+                    condition: condition_from_symbol(&period.summary.symbol_code),
+                    daylight: daylight_from_symbol(&period.summary.symbol_code),
+                    temperature: Temperature::new(details.air_temperature, TEMPERATURE_UNIT),
+                    precipitation: period
+                        .details
+                        .precipitation_amount
+                        .map(|amount| Precipitation::new(amount, PRECIPITATION_UNIT)),
+                    wind_speed: Speed::new(details.wind_speed, SPEED_UNIT),
+                    wind_direction: details.wind_from_direction.round() as u16,
+                    wind_gusts: None,
+                })
+            })
+            .take(FORECAST_HOURS)
+            .collect();
+
+        let mut days: BTreeMap<NaiveDate, Vec<&TimeStep>> = BTreeMap::new();
+        for step in timeseries {
+            days.entry(step.local_time(&offset).date())
+                .or_default()
+                .push(step);
+        }
+        let daily = days
+            .iter()
+            .take(FORECAST_DAYS)
+            .map(|(date, steps)| daily_point(*date, steps, &offset))
+            .collect();
+
+        let current = timeseries.first().map(|step| Observation {
+            time: step.local_time(&offset),
+            ..Observation::from(step)
+        });
+        Forecast {
+            current,
+            hourly,
+            daily,
+            utc_offset_seconds: offset.local_minus_utc(),
+            alerts: vec![],
+        }
+    }
+}
+
+/// Last response of the API, reused until it expires
+struct CachedForecast {
+    expires: Option<DateTime<Utc>>,
+    last_modified: Option<String>,
+    forecast: LocationForecast,
+    /// Offset of the location, looked up along with the response
+    offset: FixedOffset,
+}
+
+/// [WeatherProvider] for the MET Norway Locationforecast 2.0 API
+///
+/// The API has no location search, so Open Meteo is used for that.
+pub(crate) struct MetNo {
+    client: Client,
+    geocoder: OpenMeteo,
+    /// Last responses keyed by the rounded coordinates they were requested for
+    cache: Mutex<HashMap<(String, String), CachedForecast>>,
+}
+
+impl MetNo {
+    pub fn new(client: Client, geocoder: OpenMeteo) -> Self {
+        MetNo {
+            client,
+            geocoder,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the forecast and the UTC offset of `location`, honouring `Expires` and
+    /// `Last-Modified` of the previous response
+    async fn get_forecast(&self, location: &Location) -> Result<(LocationForecast, FixedOffset)> {
+        // met.no asks for at most 4 decimals
+        let key = (
+            format!("{:.4}", location.latitude),
+            format!("{:.4}", location.longitude),
+        );
+
+        let mut last_modified = None;
+        {
+            let cache = self.cache.lock().unwrap();
+            if let Some(cached) = cache.get(&key) {
+                if cached.expires.is_some_and(|expires| expires > Utc::now()) {
+                    debug!("Using cached met.no forecast");
+                    return Ok((cached.forecast.clone(), cached.offset));
+                }
+                last_modified = cached.last_modified.clone();
+            }
+        }
+
+        let mut request = self
+            .client
+            .get(FORECAST_URL)
+            .query(&[("lat", &key.0), ("lon", &key.1)]);
+        if let Some(ref last_modified) = last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        let response = request.send().await?;
+        let expires = header_date(response.headers(), EXPIRES);
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            debug!("met.no forecast not modified");
+            let mut cache = self.cache.lock().unwrap();
+            if let Some(cached) = cache.get_mut(&key) {
+                cached.expires = expires;
+                return Ok((cached.forecast.clone(), cached.offset));
+            }
+            return Err(Error::other("No cached forecast for 304 response."));
+        }
+
+        let response = check_status(response)?;
+        let last_modified = response
+            .headers()
+            .get(LAST_MODIFIED)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+        let forecast = response.json::<LocationForecast>().await?;
+        // The API only returns UTC times, so the offset of the location is looked up separately
+        let utc_offset_seconds = self.geocoder.utc_offset_seconds(location).await?;
+        let offset = FixedOffset::east_opt(utc_offset_seconds)
+            .ok_or_else(|| Error::other(format!("Invalid UTC offset of {}.", location.timezone)))?;
+        self.cache.lock().unwrap().insert(
+            key,
+            CachedForecast {
+                expires,
+                last_modified,
+                forecast: forecast.clone(),
+                offset,
+            },
+        );
+        Ok((forecast, offset))
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for MetNo {
+    async fn forecast(&self, location: &Location) -> Result<Forecast> {
+        debug!("get_forecast({location:?})");
+        let (forecast, offset) = self.get_forecast(location).await?;
+        Ok(forecast.to_forecast(offset))
+    }
+
+    async fn search_location(&self, name: &str, lang: &str) -> Result<Vec<Location>> {
+        self.geocoder.search_location(name, lang).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, Offset, Utc};
+
+    use crate::weather::{Intensity, WeatherCondition};
+
+    use super::{condition_from_symbol, daylight_from_symbol, LocationForecast};
+
+    #[test]
+    fn decode_locationforecast() {
+        let result: Result<LocationForecast, serde_json::Error> = serde_json::from_str(
+            include_str!("../../tests/data/metno_locationforecast_compact.json"),
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn convert_locationforecast() {
+        let response: LocationForecast = serde_json::from_str(include_str!(
+            "../../tests/data/metno_locationforecast_compact.json"
+        ))
+        .unwrap();
+        let forecast = response.to_forecast(Utc.fix());
+        let current = forecast.current.unwrap();
+        assert_eq!(current.condition, WeatherCondition::Overcast);
+        assert_eq!(forecast.hourly.len(), 12);
+        assert_eq!(forecast.daily.len(), 7);
+        assert!(forecast
+            .daily
+            .iter()
+            .all(|day| day.temperature_min.value <= day.temperature_max.value));
+    }
+
+    #[test]
+    fn convert_locationforecast_in_local_time() {
+        let response: LocationForecast = serde_json::from_str(include_str!(
+            "../../tests/data/metno_locationforecast_compact.json"
+        ))
+        .unwrap();
+        let forecast = response.to_forecast(FixedOffset::east_opt(10 * 3600).unwrap());
+        assert_eq!(forecast.utc_offset_seconds, 36000);
+        let current = forecast.current.unwrap();
+        assert_eq!(current.time.to_string(), "2024-10-22 03:00:00");
+        assert_eq!(forecast.hourly[0].time.to_string(), "2024-10-22 03:00:00");
+        assert_eq!(forecast.daily[0].date.to_string(), "2024-10-22");
+    }
+
+    #[test]
+    fn map_symbol_codes() {
+        // Symbol codes with an icon in the bundled themes map straight onto it
+        for symbol_code in [
+            "clearsky_day",
+            "fair_day",
+            "partlycloudy_day",
+            "cloudy",
+            "fog",
+            "lightrain",
+            "rain",
+            "heavyrain",
+            "lightsleet",
+            "sleet",
+            "heavysleet",
+            "lightsnow",
+            "snow",
+            "heavysnow",
+            "heavyrainandthunder",
+            "sleetandthunder",
+            "heavysleetandthunder",
+            "clearsky_night",
+            "fair_polartwilight",
+            "partlycloudy_night",
+        ] {
+            let daylight = daylight_from_symbol(symbol_code);
+            assert_eq!(
+                condition_from_symbol(symbol_code).icon_name(daylight),
+                symbol_code
+            );
+        }
+        assert_eq!(
+            condition_from_symbol("lightssnowshowersandthunder_night"),
+            WeatherCondition::SnowAndThunder(Intensity::Light)
+        );
+        assert_eq!(condition_from_symbol("tornado"), WeatherCondition::Unknown);
+    }
+}
@@ -0,0 +1,77 @@
+use std::{fmt::Display, slice::Iter, sync::Arc};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Result,
+    settings::Settings,
+    weather::{Forecast, Location},
+};
+
+pub mod brightsky;
+pub mod metno;
+pub mod nws;
+pub mod open_meteo;
+
+use brightsky::BrightSky;
+use metno::MetNo;
+use nws::Nws;
+use open_meteo::OpenMeteo;
+
+/// A source of weather data and location search results
+#[async_trait]
+pub(crate) trait WeatherProvider: Send + Sync {
+    /// Get current weather plus hourly and daily forecast for specific [Location]
+    async fn forecast(&self, location: &Location) -> Result<Forecast>;
+
+    /// Search locations by name
+    async fn search_location(&self, name: &str, lang: &str) -> Result<Vec<Location>>;
+
+    /// Store provider state worth keeping across restarts in [Settings]
+    ///
+    /// Returns `true` if the settings were changed.
+    fn save_state(&self, _settings: &mut Settings) -> bool {
+        false
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ProviderKind {
+    #[default]
+    OpenMeteo,
+    MetNo,
+    BrightSky,
+    Nws,
+}
+
+impl Display for ProviderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderKind::OpenMeteo => write!(f, "Open-Meteo"),
+            ProviderKind::MetNo => write!(f, "MET Norway"),
+            ProviderKind::BrightSky => write!(f, "Bright Sky (DWD)"),
+            ProviderKind::Nws => write!(f, "National Weather Service (US)"),
+        }
+    }
+}
+
+impl ProviderKind {
+    pub fn iterator() -> Iter<'static, ProviderKind> {
+        use ProviderKind::*;
+        static PROVIDERS: [ProviderKind; 4] = [OpenMeteo, MetNo, BrightSky, Nws];
+        PROVIDERS.iter()
+    }
+}
+
+/// Create the [WeatherProvider] selected in [Settings]
+pub(crate) fn create_provider(settings: &Settings, client: Client) -> Arc<dyn WeatherProvider> {
+    let geocoder = OpenMeteo::new(settings.open_meteo.clone(), client.clone());
+    match settings.provider {
+        ProviderKind::OpenMeteo => Arc::new(geocoder),
+        ProviderKind::MetNo => Arc::new(MetNo::new(client, geocoder)),
+        ProviderKind::BrightSky => Arc::new(BrightSky::new(client, geocoder)),
+        ProviderKind::Nws => Arc::new(Nws::new(client, geocoder, settings.nws_grid_points.clone())),
+    }
+}
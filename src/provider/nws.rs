@@ -0,0 +1,505 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, Utc};
+use log::{debug, warn};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Result,
+    http::check_status,
+    settings::Settings,
+    units::{Speed, SpeedUnit, Temperature, TemperatureUnit},
+    weather::{
+        Alert, DailyPoint, Daylight, Forecast, HourlyPoint, Intensity, Location, Observation,
+        WeatherCondition,
+    },
+};
+
+use super::{open_meteo::OpenMeteo, WeatherProvider};
+
+const API_URL: &str = "https://api.weather.gov";
+
+const FORECAST_HOURS: usize = 12;
+const FORECAST_DAYS: usize = 7;
+
+/// Forecast office grid a location belongs to
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct GridPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub grid_id: String,
+    pub grid_x: u32,
+    pub grid_y: u32,
+}
+
+impl GridPoint {
+    fn matches(&self, location: &Location) -> bool {
+        self.latitude == location.latitude && self.longitude == location.longitude
+    }
+
+    fn forecast_url(&self) -> String {
+        format!(
+            "{API_URL}/gridpoints/{}/{},{}/forecast",
+            self.grid_id, self.grid_x, self.grid_y
+        )
+    }
+
+    fn forecast_hourly_url(&self) -> String {
+        format!("{}/hourly", self.forecast_url())
+    }
+}
+
+/// Representation for NWS `/points` response object
+#[derive(Debug, Deserialize)]
+pub(crate) struct Points {
+    pub properties: PointsProperties,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PointsProperties {
+    pub grid_id: String,
+    pub grid_x: u32,
+    pub grid_y: u32,
+}
+
+/// Representation for NWS gridpoint forecast response object
+#[derive(Debug, Deserialize)]
+pub(crate) struct GridpointForecast {
+    pub properties: GridpointForecastProperties,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct GridpointForecastProperties {
+    pub periods: Vec<Period>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Period {
+    pub start_time: DateTime<FixedOffset>,
+    pub end_time: DateTime<FixedOffset>,
+    pub is_daytime: bool,
+    pub temperature: f32,
+    pub temperature_unit: String,
+    pub wind_speed: String,
+    pub wind_direction: String,
+    pub icon: String,
+    pub short_forecast: String,
+}
+
+/// Representation for NWS `/alerts/active` response object
+#[derive(Debug, Deserialize)]
+pub(crate) struct ActiveAlerts {
+    pub features: Vec<AlertFeature>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AlertFeature {
+    pub properties: AlertProperties,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AlertProperties {
+    pub event: String,
+    pub onset: Option<DateTime<FixedOffset>>,
+    pub ends: Option<DateTime<FixedOffset>>,
+    pub expires: Option<DateTime<FixedOffset>>,
+}
+
+impl From<&AlertProperties> for Alert {
+    fn from(value: &AlertProperties) -> Self {
+        Alert {
+            event: value.event.clone(),
+            onset: value.onset.map(|onset| onset.to_utc()),
+            // Alerts without a known end are valid until they expire
+            ends: value.ends.or(value.expires).map(|ends| ends.to_utc()),
+        }
+    }
+}
+
+/// Parse wind speeds like `10 mph` or `5 to 10 mph`, taking the upper value
+fn parse_wind_speed(wind_speed: &str) -> Speed {
+    let value = wind_speed
+        .split_whitespace()
+        .filter_map(|part| part.parse::<f32>().ok())
+        .next_back()
+        .unwrap_or_default();
+    let unit = if wind_speed.ends_with("km/h") {
+        SpeedUnit::KilometresPerHour
+    } else {
+        SpeedUnit::MilesPerHour
+    };
+    Speed::new(value, unit)
+}
+
+/// Convert compass points like `SSW` into degrees
+fn parse_wind_direction(wind_direction: &str) -> u16 {
+    const POINTS: [&str; 16] = [
+        "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW",
+        "NW", "NNW",
+    ];
+    POINTS
+        .iter()
+        .position(|point| *point == wind_direction)
+        .map_or(0, |index| (index as f32 * 22.5).round() as u16)
+}
+
+/// Map the condition code of an icon URL like
+/// `https://api.weather.gov/icons/land/day/rain_showers,40?size=small` to a [WeatherCondition]
+pub(crate) fn to_condition(icon: &str, short_forecast: &str) -> WeatherCondition {
+    use WeatherCondition::*;
+
+    // The first code after the day/night segment describes the main condition
+    let path = icon.split('?').next().unwrap_or_default();
+    let code = path
+        .split('/')
+        .skip_while(|segment| *segment != "day" && *segment != "night")
+        .nth(1)
+        .and_then(|segment| segment.split(',').next())
+        .unwrap_or_default();
+    let code = code.strip_prefix("wind_").unwrap_or(code);
+
+    let intensity = if short_forecast.contains("Light") {
+        Intensity::Light
+    } else if short_forecast.contains("Heavy") {
+        Intensity::Heavy
+    } else {
+        Intensity::Moderate
+    };
+
+    match code {
+        "skc" | "hot" | "cold" => ClearSky,
+        "few" => MainlyClear,
+        "sct" | "bkn" => PartlyCloudy,
+        "ovc" => Overcast,
+        // Reduced visibility
+        "fog" | "haze" | "smoke" | "dust" => Fog,
+        "rain" => Rain(intensity),
+        "rain_showers" | "rain_showers_hi" => RainShowers(intensity),
+        "fzra" | "rain_fzra" | "snow_fzra" => FreezingRain(intensity),
+        "sleet" | "rain_sleet" | "rain_snow" | "snow_sleet" => Sleet(intensity),
+        "snow" => Snow(intensity),
+        "blizzard" => Snow(Intensity::Heavy),
+        "tsra" | "tsra_sct" | "tsra_hi" | "tornado" | "hurricane" | "tropical_storm" => {
+            Thunderstorm
+        }
+        _ => Unknown,
+    }
+}
+
+impl Period {
+    fn condition(&self) -> WeatherCondition {
+        to_condition(&self.icon, &self.short_forecast)
+    }
+
+    fn temperature(&self) -> Temperature {
+        let unit = match self.temperature_unit.as_str() {
+            "C" => TemperatureUnit::Celsius,
+            _ => TemperatureUnit::Fahrenheit,
+        };
+        Temperature::new(self.temperature, unit)
+    }
+}
+
+impl From<&Period> for HourlyPoint {
+    fn from(value: &Period) -> Self {
+        HourlyPoint {
+            time: value.start_time.naive_local(),
+            condition: value.condition(),
+            daylight: Daylight::from_is_day(value.is_daytime),
+            temperature: value.temperature(),
+            precipitation: None,
+            wind_speed: parse_wind_speed(&value.wind_speed),
+            wind_direction: parse_wind_direction(&value.wind_direction),
+            wind_gusts: None,
+        }
+    }
+}
+
+/// Combine a day period and the following night period
+fn daily_point(day: Option<&Period>, night: Option<&Period>) -> Option<DailyPoint> {
+    let main = day.or(night)?;
+    let max = day.map(Period::temperature);
+    let min = night.map(Period::temperature);
+    let wind_speed_max = [day, night]
+        .into_iter()
+        .flatten()
+        .map(|period| parse_wind_speed(&period.wind_speed))
+        .max_by(|a, b| a.value.total_cmp(&b.value))?;
+
+    Some(DailyPoint {
+        date: main.start_time.date_naive(),
+        condition: main.condition(),
+        temperature_max: max.or(min)?,
+        temperature_min: min.or(max)?,
+        precipitation_sum: None,
+        wind_speed_max,
+        wind_gusts_max: None,
+        wind_direction_dominant: parse_wind_direction(&main.wind_direction),
+    })
+}
+
+impl GridpointForecast {
+    /// Hourly periods which did not end before `now`
+    fn to_hourly(&self, now: DateTime<Utc>) -> Vec<HourlyPoint> {
+        self.properties
+            .periods
+            .iter()
+            .filter(|period| period.end_time > now)
+            .take(FORECAST_HOURS)
+            .map(HourlyPoint::from)
+            .collect()
+    }
+
+    /// Day and night periods merged into days
+    fn to_daily(&self) -> Vec<DailyPoint> {
+        let mut daily = vec![];
+        let mut periods = self.properties.periods.iter().peekable();
+        while let Some(period) = periods.next() {
+            let point = if period.is_daytime {
+                let night = periods.next_if(|next| !next.is_daytime);
+                daily_point(Some(period), night)
+            } else {
+                daily_point(None, Some(period))
+            };
+            daily.extend(point);
+        }
+        daily.truncate(FORECAST_DAYS);
+        daily
+    }
+}
+
+/// [WeatherProvider] for the US National Weather Service API
+///
+/// Locations are resolved to a forecast office grid, which is kept in [Settings].
+/// The API has no location search, so Open Meteo is used for that.
+pub(crate) struct Nws {
+    client: Client,
+    geocoder: OpenMeteo,
+    /// Grids resolved so far, keyed by [Location::id]
+    grid_points: Mutex<BTreeMap<String, GridPoint>>,
+}
+
+impl Nws {
+    pub fn new(
+        client: Client,
+        geocoder: OpenMeteo,
+        grid_points: BTreeMap<String, GridPoint>,
+    ) -> Self {
+        Nws {
+            client,
+            geocoder,
+            grid_points: Mutex::new(grid_points),
+        }
+    }
+
+    /// Get the grid of [Location], resolving it with `/points` if not known yet
+    async fn get_grid_point(&self, location: &Location) -> Result<GridPoint> {
+        if let Some(grid_point) = self
+            .grid_points
+            .lock()
+            .unwrap()
+            .get(&location.id.to_string())
+            .filter(|grid_point| grid_point.matches(location))
+        {
+            return Ok(grid_point.clone());
+        }
+
+        debug!("Resolving NWS grid point");
+        // The API redirects requests with more than 4 decimals
+        let url = format!(
+            "{API_URL}/points/{:.4},{:.4}",
+            location.latitude, location.longitude
+        );
+        let response = self.client.get(url).send().await?;
+        let points = check_status(response)?.json::<Points>().await?;
+        let grid_point = GridPoint {
+            latitude: location.latitude,
+            longitude: location.longitude,
+            grid_id: points.properties.grid_id,
+            grid_x: points.properties.grid_x,
+            grid_y: points.properties.grid_y,
+        };
+        self.grid_points
+            .lock()
+            .unwrap()
+            .insert(location.id.to_string(), grid_point.clone());
+        Ok(grid_point)
+    }
+
+    async fn get_periods(&self, url: String) -> Result<GridpointForecast> {
+        let response = self.client.get(url).send().await?;
+        let forecast = check_status(response)?.json::<GridpointForecast>().await?;
+        Ok(forecast)
+    }
+
+    async fn get_alerts(&self, location: &Location) -> Result<Vec<Alert>> {
+        let url = format!(
+            "{API_URL}/alerts/active?point={:.4},{:.4}",
+            location.latitude, location.longitude
+        );
+        let response = self.client.get(url).send().await?;
+        let alerts = check_status(response)?.json::<ActiveAlerts>().await?;
+        Ok(alerts
+            .features
+            .iter()
+            .map(|feature| Alert::from(&feature.properties))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for Nws {
+    async fn forecast(&self, location: &Location) -> Result<Forecast> {
+        debug!("get_forecast({location:?})");
+        let grid_point = self.get_grid_point(location).await?;
+        let hourly = self.get_periods(grid_point.forecast_hourly_url()).await?;
+        let daily = self.get_periods(grid_point.forecast_url()).await?;
+        let utc_offset_seconds = hourly
+            .properties
+            .periods
+            .first()
+            .map_or(0, |period| period.start_time.offset().local_minus_utc());
+        let hourly = hourly.to_hourly(Utc::now());
+        // The forecast is still useful without alerts
+        let alerts = self
+            .get_alerts(location)
+            .await
+            .inspect_err(|err| warn!("Could not get alerts: {err}"))
+            .unwrap_or_default();
+        Ok(Forecast {
+            current: hourly.first().map(Observation::from),
+            hourly,
+            daily: daily.to_daily(),
+            utc_offset_seconds,
+            alerts,
+        })
+    }
+
+    async fn search_location(&self, name: &str, lang: &str) -> Result<Vec<Location>> {
+        self.geocoder.search_location(name, lang).await
+    }
+
+    fn save_state(&self, settings: &mut Settings) -> bool {
+        // Grids of removed locations are dropped
+        let mut grid_points = self.grid_points.lock().unwrap().clone();
+        grid_points.retain(|id, _| {
+            settings
+                .locations
+                .iter()
+                .any(|location| location.id.to_string() == *id)
+        });
+        if settings.nws_grid_points != grid_points {
+            settings.nws_grid_points = grid_points;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::{
+        units::{SpeedUnit, TemperatureUnit},
+        weather::{Intensity, WeatherCondition},
+    };
+
+    use super::{
+        parse_wind_direction, parse_wind_speed, to_condition, ActiveAlerts, GridpointForecast,
+        Points,
+    };
+    use crate::weather::Alert;
+
+    #[test]
+    fn decode_points() {
+        let points: Points =
+            serde_json::from_str(include_str!("../../tests/data/nws_points.json")).unwrap();
+        assert_eq!(points.properties.grid_id, "LWX");
+        assert_eq!(points.properties.grid_x, 97);
+        assert_eq!(points.properties.grid_y, 71);
+    }
+
+    #[test]
+    fn convert_forecast() {
+        let forecast: GridpointForecast =
+            serde_json::from_str(include_str!("../../tests/data/nws_forecast.json")).unwrap();
+        let daily = forecast.to_daily();
+        assert_eq!(daily.len(), 7);
+        // Starts with "Tonight", which only has a low
+        assert_eq!(daily[0].date.to_string(), "2024-10-21");
+        assert_eq!(daily[0].temperature_max.value, 52.0);
+        assert_eq!(daily[1].temperature_max.value, 71.0);
+        assert_eq!(daily[1].temperature_min.value, 55.0);
+        assert_eq!(daily[1].temperature_max.unit, TemperatureUnit::Fahrenheit);
+    }
+
+    #[test]
+    fn convert_forecast_hourly() {
+        let forecast: GridpointForecast =
+            serde_json::from_str(include_str!("../../tests/data/nws_forecast_hourly.json"))
+                .unwrap();
+        let now = DateTime::parse_from_rfc3339("2024-10-21T17:30:00-04:00")
+            .unwrap()
+            .to_utc();
+        let hourly = forecast.to_hourly(now);
+        assert_eq!(hourly.len(), 12);
+        assert_eq!(hourly[0].time.to_string(), "2024-10-21 17:00:00");
+        assert_eq!(
+            hourly[6].condition,
+            WeatherCondition::Rain(Intensity::Light)
+        );
+    }
+
+    #[test]
+    fn convert_alerts() {
+        let alerts: ActiveAlerts =
+            serde_json::from_str(include_str!("../../tests/data/nws_alerts.json")).unwrap();
+        let alerts: Vec<_> = alerts
+            .features
+            .iter()
+            .map(|feature| Alert::from(&feature.properties))
+            .collect();
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].event, "Wind Advisory");
+        let time = DateTime::parse_from_rfc3339("2024-10-21T18:00:00-04:00")
+            .unwrap()
+            .to_utc();
+        assert!(alerts[0].is_active(time));
+        // Ends at the time it expires
+        assert!(!alerts[1].is_active(time));
+        assert!(alerts[1].ends.is_some());
+    }
+
+    #[test]
+    fn parse_wind() {
+        let speed = parse_wind_speed("5 to 10 mph");
+        assert_eq!(speed.value, 10.0);
+        assert_eq!(speed.unit, SpeedUnit::MilesPerHour);
+        assert_eq!(parse_wind_direction("SSW"), 203);
+        assert_eq!(parse_wind_direction("N"), 0);
+    }
+
+    #[test]
+    fn map_icons() {
+        assert_eq!(
+            to_condition(
+                "https://api.weather.gov/icons/land/day/tsra_hi,30/tsra,50?size=medium",
+                "Chance Showers And Thunderstorms"
+            ),
+            WeatherCondition::Thunderstorm
+        );
+        assert_eq!(
+            to_condition(
+                "https://api.weather.gov/icons/land/night/wind_few?size=small",
+                "Mostly Clear"
+            ),
+            WeatherCondition::MainlyClear
+        );
+    }
+}
@@ -0,0 +1,603 @@
+use std::{fmt::Display, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use log::debug;
+use reqwest::{Client, StatusCode, Url};
+use serde::{
+    de::{value, DeserializeOwned, IntoDeserializer},
+    Deserialize, Deserializer, Serialize,
+};
+
+use crate::{
+    error::{Error, Result},
+    http::retry_after,
+    units::{Precipitation, PrecipitationUnit, Speed, SpeedUnit, Temperature, TemperatureUnit},
+    weather::{
+        DailyPoint, Daylight, Forecast, HourlyPoint, Location, Observation, WeatherCondition,
+    },
+};
+
+use super::WeatherProvider;
+
+const FORECAST_URL: &str = "https://api.open-meteo.com";
+const GEOCODING_URL: &str = "https://geocoding-api.open-meteo.com";
+const FORECAST_PATH: &str = "/v1/forecast";
+const GEOCODING_PATH: &str = "/v1/search";
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Results {
+    pub results: Vec<Location>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub(crate) struct WeatherError {
+    pub error: bool,
+    pub reason: String,
+}
+
+impl std::error::Error for WeatherError {}
+
+impl WeatherError {
+    /// Only the minutely limit resets soon enough to be worth waiting for
+    fn into_rate_limit(self, retry_after: Option<Duration>) -> Error {
+        if self.reason.starts_with("Minutely") {
+            Error::RateLimited(retry_after.or(Some(Duration::from_secs(60))))
+        } else {
+            self.into()
+        }
+    }
+}
+
+impl Display for WeatherError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WeatherError: {}", self.reason)
+    }
+}
+
+/// Representation for OpenMeteo REST weather response object
+#[derive(Deserialize, Debug)]
+pub(crate) struct WeatherResponse {
+    pub error: Option<WeatherError>,
+    #[serde(default)]
+    pub utc_offset_seconds: i32,
+    pub current_weather: Option<CurrentWeather>,
+    #[serde(default)]
+    pub current_weather_units: ResponseUnits,
+    pub current: Option<Current>,
+    #[serde(default)]
+    pub current_units: ResponseUnits,
+    pub hourly: Option<Hourly>,
+    #[serde(default)]
+    pub hourly_units: ResponseUnits,
+    pub daily: Option<Daily>,
+    #[serde(default)]
+    pub daily_units: ResponseUnits,
+}
+
+/// Units of the values in one block of a [WeatherResponse], e.g. `hourly_units`
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(try_from = "ReceivedUnits")]
+pub(crate) struct ResponseUnits {
+    pub temperature: TemperatureUnit,
+    pub wind_speed: SpeedUnit,
+    pub precipitation: PrecipitationUnit,
+}
+
+/// [ResponseUnits] as received, before checking they are known to this app
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ReceivedUnits {
+    #[serde(alias = "temperature_2m", alias = "temperature_2m_max")]
+    temperature: Option<String>,
+    #[serde(
+        alias = "windspeed",
+        alias = "wind_speed_10m",
+        alias = "wind_speed_10m_max"
+    )]
+    wind_speed: Option<String>,
+    #[serde(alias = "precipitation_sum")]
+    precipitation: Option<String>,
+}
+
+/// Missing units are the default ones, which are the requested ones, unknown units are an error
+/// rather than showing values in the wrong unit
+impl TryFrom<ReceivedUnits> for ResponseUnits {
+    type Error = String;
+
+    fn try_from(value: ReceivedUnits) -> core::result::Result<Self, Self::Error> {
+        let default = ResponseUnits::default();
+        Ok(Self {
+            temperature: parse_unit(value.temperature, default.temperature)?,
+            wind_speed: parse_unit(value.wind_speed, default.wind_speed)?,
+            precipitation: parse_unit(value.precipitation, default.precipitation)?,
+        })
+    }
+}
+
+fn parse_unit<T: DeserializeOwned>(
+    unit: Option<String>,
+    default: T,
+) -> core::result::Result<T, String> {
+    let Some(unit) = unit else {
+        return Ok(default);
+    };
+    T::deserialize(IntoDeserializer::<value::Error>::into_deserializer(
+        unit.as_str(),
+    ))
+    .map_err(|_| format!("Unknown unit {unit:?} in response"))
+}
+
+impl Default for ResponseUnits {
+    /// Open Meteo default units
+    fn default() -> Self {
+        Self {
+            temperature: TemperatureUnit::Celsius,
+            wind_speed: SpeedUnit::KilometresPerHour,
+            precipitation: PrecipitationUnit::Millimetres,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub(crate) struct Current {
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub time: NaiveDateTime,
+    pub temperature_2m: f32,
+    pub precipitation: f32,
+    pub wind_speed_10m: f32,
+    pub wind_direction_10m: u16,
+    pub wind_gusts_10m: f32,
+    pub weather_code: WeatherCondition,
+    pub is_day: Option<u8>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub(crate) struct Hourly {
+    #[serde(deserialize_with = "deserialize_datetime_vec")]
+    pub time: Vec<NaiveDateTime>,
+    pub temperature_2m: Vec<f32>,
+    pub precipitation: Vec<f32>,
+    pub wind_speed_10m: Vec<f32>,
+    pub wind_direction_10m: Vec<u16>,
+    pub wind_gusts_10m: Vec<f32>,
+    pub weather_code: Vec<WeatherCondition>,
+    #[serde(default)]
+    pub is_day: Vec<u8>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub(crate) struct Daily {
+    #[serde(deserialize_with = "deserialize_date_vec")]
+    pub time: Vec<NaiveDate>,
+    pub temperature_2m_max: Vec<f32>,
+    pub temperature_2m_min: Vec<f32>,
+    pub precipitation_sum: Vec<f32>,
+    pub wind_speed_10m_max: Vec<f32>,
+    pub wind_gusts_10m_max: Vec<f32>,
+    pub wind_direction_10m_dominant: Vec<u16>,
+    pub weather_code: Vec<WeatherCondition>,
+}
+
+/// Representation for OpenMeteo REST current_weather object
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub(crate) struct CurrentWeather {
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub time: NaiveDateTime,
+    pub temperature: f32,
+    pub windspeed: f32,
+    pub winddirection: u16,
+    pub weathercode: WeatherCondition,
+    pub is_day: Option<u8>,
+}
+
+// Funktion zum Deserialisieren einer Liste von NaiveDateTime-Werten
+fn deserialize_datetime<'de, D>(deserializer: D) -> core::result::Result<NaiveDateTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let time: String = Deserialize::deserialize(deserializer)?;
+    parse_datetime::<'de, D>(time)
+}
+
+// Funktion zum Deserialisieren einer Liste von NaiveDate-Werten
+fn deserialize_date_vec<'de, D>(deserializer: D) -> core::result::Result<Vec<NaiveDate>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: Vec<String> = Vec::deserialize(deserializer)?;
+    s.into_iter().map(parse_date::<'de, D>).collect()
+}
+
+// Funktion zum Deserialisieren einer Liste von NaiveDateTime-Werten
+fn deserialize_datetime_vec<'de, D>(
+    deserializer: D,
+) -> core::result::Result<Vec<NaiveDateTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: Vec<String> = Vec::deserialize(deserializer)?;
+    s.into_iter().map(parse_datetime::<'de, D>).collect()
+}
+
+fn parse_date<'de, D>(s: String) -> core::result::Result<NaiveDate, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    NaiveDate::parse_from_str(&s, "%Y-%m-%d").map_err(serde::de::Error::custom)
+}
+
+fn parse_datetime<'de, D>(s: String) -> core::result::Result<NaiveDateTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M").map_err(serde::de::Error::custom)
+}
+
+impl CurrentWeather {
+    fn into_observation(self, units: &ResponseUnits) -> Observation {
+        Observation {
+            time: self.time,
+            condition: self.weathercode,
+            daylight: Daylight::from_is_day(self.is_day != Some(0)),
+            temperature: Temperature::new(self.temperature, units.temperature),
+            precipitation: None,
+            wind_speed: Speed::new(self.windspeed, units.wind_speed),
+            wind_direction: self.winddirection,
+            wind_gusts: None,
+        }
+    }
+}
+
+impl Current {
+    fn into_observation(self, units: &ResponseUnits) -> Observation {
+        Observation {
+            time: self.time,
+            condition: self.weather_code,
+            daylight: Daylight::from_is_day(self.is_day != Some(0)),
+            temperature: Temperature::new(self.temperature_2m, units.temperature),
+            precipitation: Some(Precipitation::new(self.precipitation, units.precipitation)),
+            wind_speed: Speed::new(self.wind_speed_10m, units.wind_speed),
+            wind_direction: self.wind_direction_10m,
+            wind_gusts: Some(Speed::new(self.wind_gusts_10m, units.wind_speed)),
+        }
+    }
+}
+
+impl Hourly {
+    fn into_points(self, units: &ResponseUnits) -> Vec<HourlyPoint> {
+        // Only take as many points as every series provides
+        let len = [
+            self.time.len(),
+            self.temperature_2m.len(),
+            self.precipitation.len(),
+            self.wind_speed_10m.len(),
+            self.wind_direction_10m.len(),
+            self.wind_gusts_10m.len(),
+            self.weather_code.len(),
+        ]
+        .into_iter()
+        .min()
+        .unwrap_or_default();
+        (0..len)
+            .map(|i| HourlyPoint {
+                time: self.time[i],
+                condition: self.weather_code[i],
+                daylight: Daylight::from_is_day(self.is_day.get(i) != Some(&0)),
+                temperature: Temperature::new(self.temperature_2m[i], units.temperature),
+                precipitation: Some(Precipitation::new(
+                    self.precipitation[i],
+                    units.precipitation,
+                )),
+                wind_speed: Speed::new(self.wind_speed_10m[i], units.wind_speed),
+                wind_direction: self.wind_direction_10m[i],
+                wind_gusts: Some(Speed::new(self.wind_gusts_10m[i], units.wind_speed)),
+            })
+            .collect()
+    }
+}
+
+impl Daily {
+    fn into_points(self, units: &ResponseUnits) -> Vec<DailyPoint> {
+        // Only take as many points as every series provides
+        let len = [
+            self.time.len(),
+            self.temperature_2m_max.len(),
+            self.temperature_2m_min.len(),
+            self.precipitation_sum.len(),
+            self.wind_speed_10m_max.len(),
+            self.wind_gusts_10m_max.len(),
+            self.wind_direction_10m_dominant.len(),
+            self.weather_code.len(),
+        ]
+        .into_iter()
+        .min()
+        .unwrap_or_default();
+        (0..len)
+            .map(|i| DailyPoint {
+                date: self.time[i],
+                condition: self.weather_code[i],
+                temperature_max: Temperature::new(self.temperature_2m_max[i], units.temperature),
+                temperature_min: Temperature::new(self.temperature_2m_min[i], units.temperature),
+                precipitation_sum: Some(Precipitation::new(
+                    self.precipitation_sum[i],
+                    units.precipitation,
+                )),
+                wind_speed_max: Speed::new(self.wind_speed_10m_max[i], units.wind_speed),
+                wind_gusts_max: Some(Speed::new(self.wind_gusts_10m_max[i], units.wind_speed)),
+                wind_direction_dominant: self.wind_direction_10m_dominant[i],
+            })
+            .collect()
+    }
+}
+
+impl From<WeatherResponse> for Forecast {
+    fn from(value: WeatherResponse) -> Self {
+        Forecast {
+            current: value
+                .current
+                .map(|current| current.into_observation(&value.current_units))
+                .or_else(|| {
+                    value
+                        .current_weather
+                        .map(|current| current.into_observation(&value.current_weather_units))
+                }),
+            hourly: value
+                .hourly
+                .map(|hourly| hourly.into_points(&value.hourly_units))
+                .unwrap_or_default(),
+            daily: value
+                .daily
+                .map(|daily| daily.into_points(&value.daily_units))
+                .unwrap_or_default(),
+            utc_offset_seconds: value.utc_offset_seconds,
+            alerts: vec![],
+        }
+    }
+}
+
+/// Open Meteo API endpoints, changeable to use self-hosted instances or the commercial API
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct OpenMeteoConfig {
+    /// Base URL of the forecast API
+    pub forecast_url: String,
+    /// Base URL of the geocoding API
+    pub geocoding_url: String,
+    /// Key for `customer-api.open-meteo.com`, empty if not needed
+    pub api_key: String,
+}
+
+impl Default for OpenMeteoConfig {
+    fn default() -> Self {
+        Self {
+            forecast_url: FORECAST_URL.into(),
+            geocoding_url: GEOCODING_URL.into(),
+            api_key: String::new(),
+        }
+    }
+}
+
+impl OpenMeteoConfig {
+    fn url<V: AsRef<str>>(&self, base_url: &str, path: &str, params: &[(&str, V)]) -> Result<Url> {
+        let mut url = Url::parse(&format!("{}{path}", base_url.trim_end_matches('/')))
+            .map_err(Error::other)?;
+        url.query_pairs_mut()
+            .extend_pairs(params.iter().map(|(key, value)| (key, value.as_ref())));
+        if !self.api_key.is_empty() {
+            url.query_pairs_mut().append_pair("apikey", &self.api_key);
+        }
+        Ok(url)
+    }
+
+    fn forecast_url<V: AsRef<str>>(&self, params: &[(&str, V)]) -> Result<Url> {
+        self.url(&self.forecast_url, FORECAST_PATH, params)
+    }
+
+    fn geocoding_url<V: AsRef<str>>(&self, params: &[(&str, V)]) -> Result<Url> {
+        self.url(&self.geocoding_url, GEOCODING_PATH, params)
+    }
+}
+
+/// Time zone of `location`, or the one at its coordinates if unknown
+fn timezone(location: &Location) -> &str {
+    match location.timezone.as_str() {
+        "" => "auto",
+        timezone => timezone,
+    }
+}
+
+/// [WeatherProvider] for the Open Meteo REST API
+pub(crate) struct OpenMeteo {
+    config: OpenMeteoConfig,
+    client: Client,
+}
+
+impl OpenMeteo {
+    pub fn new(config: OpenMeteoConfig, client: Client) -> Self {
+        OpenMeteo { config, client }
+    }
+
+    /// Current UTC offset of the time zone of `location`
+    pub async fn utc_offset_seconds(&self, location: &Location) -> Result<i32> {
+        let params = [
+            ("latitude", location.latitude.to_string()),
+            ("longitude", location.longitude.to_string()),
+            ("timezone", timezone(location).into()),
+            ("forecast_days", "1".into()),
+        ];
+        let url = self.config.forecast_url(&params)?;
+        Ok(self.get_weather(url).await?.utc_offset_seconds)
+    }
+
+    async fn get_weather(&self, url: Url) -> Result<WeatherResponse> {
+        let response = self.client.get(url).send().await?;
+        let status = response.status();
+        if status.is_server_error() {
+            response.error_for_status_ref()?;
+        }
+        let retry_after = retry_after(response.headers(), Utc::now());
+        let response = response.json::<WeatherResponse>().await?;
+        match response.error {
+            Some(error) if status == StatusCode::TOO_MANY_REQUESTS => {
+                Err(error.into_rate_limit(retry_after))
+            }
+            Some(error) => Err(error.into()),
+            None => Ok(response),
+        }
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for OpenMeteo {
+    async fn forecast(&self, location: &Location) -> Result<Forecast> {
+        debug!("get_forecast({location:?})");
+        let params = [
+            ("latitude", location.latitude.to_string()),
+            ("longitude", location.longitude.to_string()),
+            ("current", "temperature_2m,precipitation,weather_code,wind_speed_10m,wind_direction_10m,wind_gusts_10m,is_day".into()),
+            ("hourly", "temperature_2m,precipitation,weather_code,wind_speed_10m,wind_direction_10m,wind_gusts_10m,is_day".into()),
+            ("daily", "weather_code,temperature_2m_max,temperature_2m_min,precipitation_sum,precipitation_hours,precipitation_probability_max,wind_speed_10m_max,wind_gusts_10m_max,wind_direction_10m_dominant".into()),
+            ("timezone", timezone(location).into()),
+            ("forecast_days", "7".into()),
+            ("forecast_hours", "12".into()),
+        ];
+        let url = self.config.forecast_url(&params)?;
+        let response = self.get_weather(url).await?;
+        Ok(response.into())
+    }
+
+    async fn search_location(&self, name: &str, lang: &str) -> Result<Vec<Location>> {
+        let params = [
+            ("name", name),
+            ("language", lang),
+            ("count", "10"),
+            ("format", "json"),
+        ];
+        let url = self.config.geocoding_url(&params)?;
+        let response = self.client.get(url).send().await?.json::<Results>().await?;
+        Ok(response.results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        units::{PrecipitationUnit, SpeedUnit, TemperatureUnit},
+        weather::{Daylight, Forecast},
+    };
+
+    use super::{OpenMeteoConfig, ResponseUnits, WeatherResponse};
+
+    #[test]
+    fn decode_weatherresponse() {
+        let result: Result<WeatherResponse, serde_json::Error> = serde_json::from_str(
+            include_str!("../../tests/data/weather_response_forecast.json"),
+        );
+        assert!(matches!(result, Ok(_)));
+    }
+
+    #[test]
+    fn convert_weatherresponse() {
+        let response: WeatherResponse = serde_json::from_str(include_str!(
+            "../../tests/data/weather_response_forecast.json"
+        ))
+        .unwrap();
+        assert_eq!(response.daily_units, ResponseUnits::default());
+        let forecast = Forecast::from(response);
+        assert!(forecast.current.is_some());
+        assert_eq!(forecast.hourly.len(), 1);
+        assert_eq!(forecast.daily.len(), 1);
+    }
+
+    #[test]
+    fn convert_mismatched_hourly() {
+        let response: WeatherResponse = serde_json::from_str(
+            r#"{"hourly": {
+                "time": ["2024-10-21T17:00", "2024-10-21T18:00"],
+                "temperature_2m": [17, 16],
+                "precipitation": [0],
+                "weather_code": [3, 3],
+                "wind_speed_10m": [5.8, 6.1],
+                "wind_direction_10m": [180, 190],
+                "wind_gusts_10m": [13.3, 14.0]
+            }}"#,
+        )
+        .unwrap();
+        let forecast = Forecast::from(response);
+        assert_eq!(forecast.hourly.len(), 1);
+    }
+
+    #[test]
+    fn convert_response_units() {
+        let response: WeatherResponse = serde_json::from_str(
+            r#"{"hourly_units": {
+                "time": "iso8601",
+                "temperature_2m": "°F",
+                "precipitation": "inch",
+                "weather_code": "wmo code",
+                "wind_speed_10m": "mp/h",
+                "wind_direction_10m": "°",
+                "wind_gusts_10m": "mp/h"
+            }, "hourly": {
+                "time": ["2024-10-21T17:00"],
+                "temperature_2m": [62.6],
+                "precipitation": [0.02],
+                "weather_code": [61],
+                "wind_speed_10m": [3.6],
+                "wind_direction_10m": [180],
+                "wind_gusts_10m": [8.3],
+                "is_day": [0]
+            }}"#,
+        )
+        .unwrap();
+        let forecast = Forecast::from(response);
+        let point = &forecast.hourly[0];
+        assert_eq!(point.daylight, Daylight::Night);
+        assert_eq!(point.temperature.unit, TemperatureUnit::Fahrenheit);
+        assert_eq!(point.wind_speed.unit, SpeedUnit::MilesPerHour);
+        assert_eq!(
+            point.precipitation.map(|p| p.unit),
+            Some(PrecipitationUnit::Inches)
+        );
+    }
+
+    #[test]
+    fn reject_unknown_units() {
+        let units: ResponseUnits =
+            serde_json::from_str(r#"{"time": "iso8601", "wind_speed_10m": "m/s"}"#).unwrap();
+        assert_eq!(units.temperature, TemperatureUnit::Celsius);
+        assert_eq!(units.wind_speed, SpeedUnit::MetresPerSecond);
+        assert_eq!(units.precipitation, PrecipitationUnit::Millimetres);
+        let result = serde_json::from_str::<ResponseUnits>(
+            r#"{"temperature_2m": "K", "wind_speed_10m": "m/s"}"#,
+        );
+        assert!(result.unwrap_err().to_string().contains(r#""K""#));
+    }
+
+    #[test]
+    fn build_urls() {
+        let config = OpenMeteoConfig {
+            forecast_url: "http://localhost:8080/open-meteo/".into(),
+            api_key: "secret".into(),
+            ..Default::default()
+        };
+        let url = config.forecast_url(&[("latitude", "52.52")]).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "http://localhost:8080/open-meteo/v1/forecast?latitude=52.52&apikey=secret"
+        );
+        let url = OpenMeteoConfig::default()
+            .geocoding_url(&[("name", "Berlin")])
+            .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://geocoding-api.open-meteo.com/v1/search?name=Berlin"
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use std::{future::Future, time::Duration};
+
+use log::debug;
+use rand::Rng;
+
+use crate::error::{Error, Result};
+
+/// Repeats failed requests with exponential backoff
+pub(crate) struct RetryPolicy {
+    /// Attempts including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further one
+    pub base_delay: Duration,
+    /// Longest delay to wait, also for `Retry-After`
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Send the request only once, for requests the user is waiting for
+    pub fn once() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Delay before retry number `retry`, or none if the error is not worth retrying
+    fn delay(&self, retry: u32, error: &Error) -> Option<Duration> {
+        if retry >= self.max_attempts || !error.is_transient() {
+            return None;
+        }
+        if let Some(retry_after) = error.retry_after() {
+            return (retry_after <= self.max_delay).then_some(retry_after);
+        }
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry - 1))
+            .min(self.max_delay);
+        // Random jitter in the upper half keeps clients from retrying in lockstep
+        Some(rand::thread_rng().gen_range(backoff / 2..=backoff))
+    }
+
+    /// Run `f` until it succeeds or the error is not worth retrying
+    pub async fn run<T, F, Fut>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut retry = 1;
+        loop {
+            match f().await {
+                Ok(value) => return Ok(value),
+                Err(err) => match self.delay(retry, &err) {
+                    Some(delay) => {
+                        debug!("Request failed: {err}. Retrying in {delay:?}");
+                        tokio::time::sleep(delay).await;
+                        retry += 1;
+                    }
+                    None => return Err(err),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, time::Duration};
+
+    use crate::error::Error;
+
+    use super::RetryPolicy;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(10),
+        }
+    }
+
+    #[test]
+    fn backoff_delays() {
+        let policy = RetryPolicy::default();
+        let error = Error::RateLimited(None);
+        for retry in 1..policy.max_attempts {
+            let delay = policy.delay(retry, &error).unwrap();
+            let backoff = (policy.base_delay * 2u32.pow(retry - 1)).min(policy.max_delay);
+            assert!(delay >= backoff / 2 && delay <= backoff);
+        }
+        assert_eq!(policy.delay(policy.max_attempts, &error), None);
+        assert_eq!(
+            policy.delay(1, &Error::RateLimited(Some(Duration::from_secs(30)))),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            policy.delay(1, &Error::RateLimited(Some(Duration::from_secs(3600)))),
+            None
+        );
+        assert_eq!(policy.delay(1, &Error::NoSettings), None);
+    }
+
+    #[tokio::test]
+    async fn retry_transient_errors() {
+        let attempts = Cell::new(0);
+        let result = policy()
+            .run(|| async {
+                attempts.set(attempts.get() + 1);
+                if attempts.get() < 3 {
+                    Err(Error::RateLimited(None))
+                } else {
+                    Ok(attempts.get())
+                }
+            })
+            .await;
+        assert!(matches!(result, Ok(3)));
+
+        attempts.set(0);
+        let result = policy()
+            .run(|| async {
+                attempts.set(attempts.get() + 1);
+                Err::<(), _>(Error::RateLimited(None))
+            })
+            .await;
+        assert!(matches!(result, Err(Error::RateLimited(_))));
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn skip_permanent_errors() {
+        let attempts = Cell::new(0);
+        let result = policy()
+            .run(|| async {
+                attempts.set(attempts.get() + 1);
+                Err::<(), _>(Error::NoSettings)
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+}
@@ -1,25 +1,69 @@
-use std::{fs, path::PathBuf};
+use std::{collections::BTreeMap, fs, path::PathBuf};
 
-use crate::{gui::IconTheme, weather::Location, Result};
+use crate::{
+    gui::{color_scheme::ColorSchemeSetting, icon_theme::IconTheme, ForecastTime},
+    http::HttpSettings,
+    provider::{nws::GridPoint, open_meteo::OpenMeteoConfig, ProviderKind},
+    units::UnitSettings,
+    weather::Location,
+    Result,
+};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub(crate) struct Settings {
-    pub location: Location,
+    /// Saved locations, in the order shown in the tray menu
+    #[serde(default)]
+    pub locations: Vec<Location>,
+    /// Index of the location in [Settings::locations] weather is shown for
+    #[serde(default)]
+    pub active_location: usize,
+    /// Ids of [Settings::locations] shown with a tray icon of their own
+    #[serde(default)]
+    pub pinned: Vec<u32>,
+    /// Single location of settings written before multiple locations were supported
+    #[serde(default, rename = "location", skip_serializing)]
+    legacy_location: Option<Location>,
     pub update_interval: u64,
     pub icon_theme: IconTheme,
+    /// Color scheme of the taskbar monochrome icons are drawn for
+    #[serde(default)]
+    pub color_scheme: ColorSchemeSetting,
+    #[serde(default)]
+    pub forecast_time: ForecastTime,
+    #[serde(default)]
+    pub units: UnitSettings,
     #[serde(default)]
     pub autorun_enabled: bool,
+    #[serde(default)]
+    pub provider: ProviderKind,
+    /// NWS forecast grids resolved for [Settings::locations], keyed by [Location::id]
+    #[serde(default)]
+    pub nws_grid_points: BTreeMap<String, GridPoint>,
+    #[serde(default)]
+    pub open_meteo: OpenMeteoConfig,
+    #[serde(default)]
+    pub http: HttpSettings,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
-            location: Default::default(),
+            locations: vec![],
+            active_location: 0,
+            pinned: vec![],
+            legacy_location: None,
             update_interval: 15,
-            icon_theme: IconTheme::Metno,
+            icon_theme: Default::default(),
+            color_scheme: Default::default(),
+            forecast_time: Default::default(),
+            units: Default::default(),
             autorun_enabled: false,
+            provider: ProviderKind::OpenMeteo,
+            nws_grid_points: BTreeMap::new(),
+            open_meteo: Default::default(),
+            http: Default::default(),
         }
     }
 }
@@ -29,6 +73,78 @@ impl Settings {
         *self = new_settings.clone();
     }
 
+    /// The active location
+    pub fn location(&self) -> Option<&Location> {
+        self.locations.get(self.active_location)
+    }
+
+    /// Pinned locations other than the active one
+    pub fn pinned_locations(&self) -> impl Iterator<Item = &Location> {
+        self.locations
+            .iter()
+            .enumerate()
+            .filter(|(index, location)| {
+                *index != self.active_location && self.pinned.contains(&location.id)
+            })
+            .map(|(_, location)| location)
+    }
+
+    pub fn is_pinned(&self, location: &Location) -> bool {
+        self.pinned.contains(&location.id)
+    }
+
+    pub fn set_pinned(&mut self, location: &Location, pinned: bool) {
+        self.pinned.retain(|id| *id != location.id);
+        if pinned {
+            self.pinned.push(location.id);
+        }
+    }
+
+    /// Add a location unless one with the same id is saved already
+    pub fn add_location(&mut self, location: Location) -> bool {
+        if self.locations.iter().any(|other| other.id == location.id) {
+            return false;
+        }
+        self.locations.push(location);
+        true
+    }
+
+    /// Remove a location, keeping the active one selected if possible
+    pub fn remove_location(&mut self, index: usize) {
+        if index >= self.locations.len() {
+            return;
+        }
+        let location = self.locations.remove(index);
+        if !self.locations.iter().any(|other| other.id == location.id) {
+            self.set_pinned(&location, false);
+        }
+        if index < self.active_location || self.active_location >= self.locations.len() {
+            self.active_location = self.active_location.saturating_sub(1);
+        }
+    }
+
+    /// Swap a location with its successor, keeping the active one selected
+    pub fn move_location_down(&mut self, index: usize) {
+        if index + 1 >= self.locations.len() {
+            return;
+        }
+        self.locations.swap(index, index + 1);
+        if self.active_location == index {
+            self.active_location += 1;
+        } else if self.active_location == index + 1 {
+            self.active_location -= 1;
+        }
+    }
+
+    fn migrate(&mut self) {
+        if let Some(location) = self.legacy_location.take() {
+            if self.locations.is_empty() && location.id != 0 {
+                self.locations.push(location);
+                self.active_location = 0;
+            }
+        }
+    }
+
     fn get_path(&self) -> PathBuf {
         if let Some(proj_dirs) = ProjectDirs::from("de", "osor", "TrayWeather") {
             let config_dir = proj_dirs.config_dir();
@@ -40,7 +156,8 @@ impl Settings {
 
     pub fn load(&mut self) -> Result<()> {
         let settings_string = fs::read_to_string(self.get_path())?;
-        let settings: Settings = toml::from_str(&settings_string)?;
+        let mut settings: Settings = toml::from_str(&settings_string)?;
+        settings.migrate();
         *self = settings;
         Ok(())
     }
@@ -55,3 +172,91 @@ impl Settings {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::weather::Location;
+
+    use super::Settings;
+
+    fn location(id: u32) -> Location {
+        Location {
+            id,
+            name: id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn migrate_single_location() {
+        let mut settings: Settings = toml::from_str(
+            r#"
+            update_interval = 15
+            icon_theme = "Metno"
+
+            [location]
+            id = 2950159
+            name = "Berlin"
+            latitude = 52.52437
+            longitude = 13.41053
+            elevation = 74.0
+            feature_code = "PPLC"
+            country_code = "DE"
+            timezone = "Europe/Berlin"
+            country_id = 2921044
+            country = "Deutschland"
+            "#,
+        )
+        .unwrap();
+        settings.migrate();
+        assert_eq!(settings.locations.len(), 1);
+        assert_eq!(settings.location().unwrap().name, "Berlin");
+        assert!(!toml::to_string(&settings).unwrap().contains("[location]"));
+    }
+
+    #[test]
+    fn edit_locations() {
+        let mut settings = Settings::default();
+        for id in 1..=3 {
+            settings.add_location(location(id));
+        }
+        settings.active_location = 1;
+        settings.set_pinned(&location(1), true);
+        settings.set_pinned(&location(2), true);
+        assert_eq!(
+            settings
+                .pinned_locations()
+                .map(|l| l.id)
+                .collect::<Vec<_>>(),
+            vec![1]
+        );
+
+        settings.move_location_down(1);
+        assert_eq!(settings.location().unwrap().id, 2);
+        assert_eq!(settings.active_location, 2);
+
+        settings.move_location_down(0);
+        assert_eq!(settings.location().unwrap().id, 2);
+
+        settings.remove_location(0);
+        assert_eq!(settings.location().unwrap().id, 2);
+
+        settings.remove_location(1);
+        assert_eq!(settings.location().unwrap().id, 1);
+        assert_eq!(settings.pinned, vec![1]);
+        settings.remove_location(0);
+        assert!(settings.location().is_none());
+    }
+
+    #[test]
+    fn skip_duplicate_locations() {
+        let mut settings = Settings::default();
+        assert!(settings.add_location(location(1)));
+        assert!(settings.add_location(location(2)));
+        assert!(!settings.add_location(location(1)));
+        assert_eq!(
+            settings.locations.iter().map(|l| l.id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use std::{fmt::Display, slice::Iter};
+
+use serde::{Deserialize, Serialize};
+
+/// Unit of a temperature value
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TemperatureUnit {
+    #[serde(alias = "°C")]
+    Celsius,
+    #[serde(alias = "°F")]
+    Fahrenheit,
+}
+
+/// Unit of a wind speed value
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SpeedUnit {
+    #[serde(alias = "km/h")]
+    KilometresPerHour,
+    #[serde(alias = "m/s")]
+    MetresPerSecond,
+    #[serde(alias = "mph", alias = "mp/h")]
+    MilesPerHour,
+    #[serde(alias = "kn")]
+    Knots,
+    #[serde(alias = "Bft")]
+    Beaufort,
+}
+
+/// Unit of a precipitation amount
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PrecipitationUnit {
+    #[serde(alias = "mm")]
+    Millimetres,
+    #[serde(alias = "in", alias = "inch")]
+    Inches,
+}
+
+/// Temperature value tagged with its [TemperatureUnit]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Temperature {
+    pub value: f32,
+    pub unit: TemperatureUnit,
+}
+
+/// Wind speed value tagged with its [SpeedUnit]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Speed {
+    pub value: f32,
+    pub unit: SpeedUnit,
+}
+
+/// Precipitation amount tagged with its [PrecipitationUnit]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Precipitation {
+    pub value: f32,
+    pub unit: PrecipitationUnit,
+}
+
+/// Units values are shown in
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UnitSettings {
+    pub temperature: TemperatureUnit,
+    pub speed: SpeedUnit,
+    pub precipitation: PrecipitationUnit,
+}
+
+impl Default for UnitSettings {
+    fn default() -> Self {
+        Self {
+            temperature: TemperatureUnit::Celsius,
+            speed: SpeedUnit::KilometresPerHour,
+            precipitation: PrecipitationUnit::Millimetres,
+        }
+    }
+}
+
+impl Display for TemperatureUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+        })
+    }
+}
+
+impl Display for SpeedUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SpeedUnit::KilometresPerHour => "km/h",
+            SpeedUnit::MetresPerSecond => "m/s",
+            SpeedUnit::MilesPerHour => "mph",
+            SpeedUnit::Knots => "kn",
+            SpeedUnit::Beaufort => "Bft",
+        })
+    }
+}
+
+impl Display for PrecipitationUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PrecipitationUnit::Millimetres => "mm",
+            PrecipitationUnit::Inches => "in",
+        })
+    }
+}
+
+impl TemperatureUnit {
+    pub fn iterator() -> Iter<'static, TemperatureUnit> {
+        use TemperatureUnit::*;
+        static UNITS: [TemperatureUnit; 2] = [Celsius, Fahrenheit];
+        UNITS.iter()
+    }
+}
+
+impl SpeedUnit {
+    pub fn iterator() -> Iter<'static, SpeedUnit> {
+        use SpeedUnit::*;
+        static UNITS: [SpeedUnit; 5] = [
+            KilometresPerHour,
+            MetresPerSecond,
+            MilesPerHour,
+            Knots,
+            Beaufort,
+        ];
+        UNITS.iter()
+    }
+}
+
+impl PrecipitationUnit {
+    pub fn iterator() -> Iter<'static, PrecipitationUnit> {
+        use PrecipitationUnit::*;
+        static UNITS: [PrecipitationUnit; 2] = [Millimetres, Inches];
+        UNITS.iter()
+    }
+}
+
+impl Temperature {
+    pub fn new(value: f32, unit: TemperatureUnit) -> Self {
+        Self { value, unit }
+    }
+
+    pub fn convert(self, unit: TemperatureUnit) -> Self {
+        use TemperatureUnit::*;
+        let celsius = match self.unit {
+            Celsius => self.value,
+            Fahrenheit => (self.value - 32.0) * 5.0 / 9.0,
+        };
+        let value = match unit {
+            Celsius => celsius,
+            Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+        };
+        Self::new(value, unit)
+    }
+}
+
+impl Speed {
+    pub fn new(value: f32, unit: SpeedUnit) -> Self {
+        Self { value, unit }
+    }
+
+    pub fn convert(self, unit: SpeedUnit) -> Self {
+        use SpeedUnit::*;
+        let metres_per_second = match self.unit {
+            KilometresPerHour => self.value / 3.6,
+            MetresPerSecond => self.value,
+            MilesPerHour => self.value * 0.44704,
+            Knots => self.value * 0.514444,
+            Beaufort => 0.836 * self.value.powf(1.5),
+        };
+        let value = match unit {
+            KilometresPerHour => metres_per_second * 3.6,
+            MetresPerSecond => metres_per_second,
+            MilesPerHour => metres_per_second / 0.44704,
+            Knots => metres_per_second / 0.514444,
+            Beaufort => (metres_per_second / 0.836)
+                .powf(2.0 / 3.0)
+                .round()
+                .min(12.0),
+        };
+        Self::new(value, unit)
+    }
+}
+
+impl Precipitation {
+    pub fn new(value: f32, unit: PrecipitationUnit) -> Self {
+        Self { value, unit }
+    }
+
+    pub fn convert(self, unit: PrecipitationUnit) -> Self {
+        use PrecipitationUnit::*;
+        let value = match (self.unit, unit) {
+            (Millimetres, Inches) => self.value / 25.4,
+            (Inches, Millimetres) => self.value * 25.4,
+            _ => self.value,
+        };
+        Self::new(value, unit)
+    }
+}
+
+impl Display for Temperature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1} {}", self.value, self.unit)
+    }
+}
+
+impl Display for Speed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.unit {
+            SpeedUnit::Beaufort => write!(f, "{} {:.0}", self.unit, self.value),
+            _ => write!(f, "{:.0} {}", self.value, self.unit),
+        }
+    }
+}
+
+impl Display for Precipitation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.unit {
+            PrecipitationUnit::Millimetres => write!(f, "{:.1} {}", self.value, self.unit),
+            PrecipitationUnit::Inches => write!(f, "{:.2} {}", self.value, self.unit),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Precipitation, PrecipitationUnit, Speed, SpeedUnit, Temperature, TemperatureUnit};
+
+    #[test]
+    fn convert_temperature() {
+        let temperature = Temperature::new(20.0, TemperatureUnit::Celsius);
+        assert_eq!(temperature.convert(TemperatureUnit::Fahrenheit).value, 68.0);
+        assert_eq!(
+            temperature
+                .convert(TemperatureUnit::Fahrenheit)
+                .convert(TemperatureUnit::Celsius),
+            temperature
+        );
+        assert_eq!(
+            temperature.convert(TemperatureUnit::Fahrenheit).to_string(),
+            "68.0 °F"
+        );
+    }
+
+    #[test]
+    fn convert_speed() {
+        let speed = Speed::new(36.0, SpeedUnit::KilometresPerHour);
+        assert_eq!(speed.convert(SpeedUnit::MetresPerSecond).value, 10.0);
+        assert_eq!(speed.convert(SpeedUnit::Beaufort).value, 5.0);
+        assert_eq!(speed.convert(SpeedUnit::Beaufort).to_string(), "Bft 5");
+        assert_eq!(
+            Speed::new(100.0, SpeedUnit::MetresPerSecond)
+                .convert(SpeedUnit::Beaufort)
+                .value,
+            12.0
+        );
+        assert_eq!(speed.convert(SpeedUnit::Knots).to_string(), "19 kn");
+    }
+
+    #[test]
+    fn convert_precipitation() {
+        let precipitation = Precipitation::new(25.4, PrecipitationUnit::Millimetres);
+        assert_eq!(precipitation.convert(PrecipitationUnit::Inches).value, 1.0);
+        assert_eq!(
+            precipitation.convert(PrecipitationUnit::Inches).to_string(),
+            "1.00 in"
+        );
+    }
+}
@@ -1,13 +1,15 @@
-use std::{borrow::Cow, fmt::Display};
+use std::borrow::Cow;
 
-use crate::error::{Error, Result};
-use chrono::{NaiveDate, NaiveDateTime};
-use image::load_from_memory_with_format;
-use log::debug;
-use reqwest::Url;
+use crate::{
+    error::{Error, Result},
+    units::{Precipitation, Speed, Temperature},
+};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use image::{imageops, ImageFormat, RgbaImage};
+use resvg::{tiny_skia, usvg};
 use rust_embed::Embed;
 use rust_i18n::t;
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{Deserialize, Serialize};
 use tray_icon::Icon;
 
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
@@ -52,284 +54,694 @@ impl Location {
     }
 }
 
-#[derive(Debug, Deserialize)]
-pub(crate) struct Results {
-    pub results: Vec<Location>,
+/// Intensity of a weather phenomenon
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Intensity {
+    Light,
+    Moderate,
+    Heavy,
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-pub(crate) struct WeatherError {
-    pub error: bool,
-    pub reason: String,
+/// Kind of falling precipitation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PrecipitationKind {
+    Drizzle,
+    Rain,
+    FreezingRain,
+    Sleet,
+    Snow,
+    Hail,
 }
 
-impl std::error::Error for WeatherError {}
+/// Weather condition based on the WMO weather interpretation codes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "ConditionCode", into = "ConditionCode")]
+pub(crate) enum WeatherCondition {
+    ClearSky,
+    MainlyClear,
+    PartlyCloudy,
+    Overcast,
+    Fog,
+    RimeFog,
+    Drizzle(Intensity),
+    FreezingDrizzle(Intensity),
+    Rain(Intensity),
+    FreezingRain(Intensity),
+    Sleet(Intensity),
+    Snow(Intensity),
+    SnowGrains,
+    RainShowers(Intensity),
+    SnowShowers(Intensity),
+    Thunderstorm,
+    ThunderstormWithHail(Intensity),
+    RainAndThunder(Intensity),
+    SleetAndThunder(Intensity),
+    SnowAndThunder(Intensity),
+    Unknown,
+}
+
+/// Serialized form of [WeatherCondition]
+///
+/// Open Meteo sends WMO codes, cached forecasts contain the variant name.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum ConditionCode {
+    Wmo(u16),
+    Name(String),
+}
 
-impl Display for WeatherError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "WeatherError: {}", self.reason)
+/// Sun position at a specific time, choosing between day and night icons
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) enum Daylight {
+    #[default]
+    Day,
+    Night,
+    /// Sun just below the horizon during polar day or night
+    PolarTwilight,
+}
+
+impl Daylight {
+    pub fn from_is_day(is_day: bool) -> Self {
+        if is_day {
+            Daylight::Day
+        } else {
+            Daylight::Night
+        }
+    }
+
+    /// Suffix of icon names like `clearsky_night`
+    fn icon_suffix(&self) -> &str {
+        match self {
+            Daylight::Day => "_day",
+            Daylight::Night => "_night",
+            Daylight::PolarTwilight => "_polartwilight",
+        }
     }
 }
 
-/// Representation for OpenMeteo REST weather response object
-#[derive(Deserialize, Debug)]
-pub(crate) struct WeatherResponse {
-    pub error: Option<WeatherError>,
-    pub current_weather: Option<CurrentWeather>,
-    pub current: Option<Current>,
-    pub hourly: Option<Hourly>,
-    pub daily: Option<Daily>,
+/// Weather conditions observed at a specific time
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Observation {
+    pub time: NaiveDateTime,
+    pub condition: WeatherCondition,
+    #[serde(default)]
+    pub daylight: Daylight,
+    pub temperature: Temperature,
+    pub precipitation: Option<Precipitation>,
+    pub wind_speed: Speed,
+    pub wind_direction: u16,
+    pub wind_gusts: Option<Speed>,
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-pub(crate) struct Current {
-    #[serde(deserialize_with = "deserialize_datetime")]
+/// Forecast for a single hour
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct HourlyPoint {
     pub time: NaiveDateTime,
-    pub temperature_2m: f32,
-    pub precipitation: f32,
-    pub wind_speed_10m: f32,
-    pub wind_direction_10m: u16,
-    pub wind_gusts_10m: f32,
-    pub weather_code: u16,
+    pub condition: WeatherCondition,
+    #[serde(default)]
+    pub daylight: Daylight,
+    pub temperature: Temperature,
+    pub precipitation: Option<Precipitation>,
+    pub wind_speed: Speed,
+    pub wind_direction: u16,
+    pub wind_gusts: Option<Speed>,
+}
+
+/// Forecast for a single day
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct DailyPoint {
+    pub date: NaiveDate,
+    pub condition: WeatherCondition,
+    pub temperature_max: Temperature,
+    pub temperature_min: Temperature,
+    pub precipitation_sum: Option<Precipitation>,
+    pub wind_speed_max: Speed,
+    pub wind_gusts_max: Option<Speed>,
+    pub wind_direction_dominant: u16,
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-pub(crate) struct Hourly {
-    #[serde(deserialize_with = "deserialize_datetime_vec")]
-    pub time: Vec<NaiveDateTime>,
-    pub temperature_2m: Vec<f32>,
-    pub precipitation: Vec<f32>,
-    pub wind_speed_10m: Vec<f32>,
-    pub wind_direction_10m: Vec<u16>,
-    pub wind_gusts_10m: Vec<f32>,
-    pub weather_code: Vec<u16>,
+/// Weather warning issued for the location
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Alert {
+    pub event: String,
+    pub onset: Option<DateTime<Utc>>,
+    pub ends: Option<DateTime<Utc>>,
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-pub(crate) struct Daily {
-    #[serde(deserialize_with = "deserialize_date_vec")]
-    pub time: Vec<NaiveDate>,
-    pub temperature_2m_max: Vec<f32>,
-    pub temperature_2m_min: Vec<f32>,
-    pub precipitation_sum: Vec<f32>,
-    pub wind_speed_10m_max: Vec<f32>,
-    pub wind_gusts_10m_max: Vec<f32>,
-    pub wind_direction_10m_dominant: Vec<u16>,
-    pub weather_code: Vec<u16>,
+/// Current weather with hourly and daily forecast
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub(crate) struct Forecast {
+    pub current: Option<Observation>,
+    pub hourly: Vec<HourlyPoint>,
+    pub daily: Vec<DailyPoint>,
+    /// Offset of the time zone all times and dates above are given in
+    #[serde(default)]
+    pub utc_offset_seconds: i32,
+    #[serde(default)]
+    pub alerts: Vec<Alert>,
 }
 
-/// Representation for OpenMeteo REST current_weather object
-#[allow(dead_code)]
-#[derive(Deserialize, Debug)]
-pub(crate) struct CurrentWeather {
-    pub temperature: f32,
-    pub windspeed: f32,
-    pub winddirection: u16,
-    pub weathercode: u16,
+impl Alert {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.onset.is_none_or(|onset| onset <= now) && self.ends.is_none_or(|ends| now < ends)
+    }
 }
 
-// Funktion zum Deserialisieren einer Liste von NaiveDateTime-Werten
-fn deserialize_datetime<'de, D>(deserializer: D) -> core::result::Result<NaiveDateTime, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let time: String = Deserialize::deserialize(deserializer)?;
-    parse_datetime::<'de, D>(time)
+impl Forecast {
+    pub fn utc_offset(&self) -> FixedOffset {
+        FixedOffset::east_opt(self.utc_offset_seconds).unwrap_or(Utc.fix())
+    }
+
+    /// Attach the forecast's time zone to one of its times
+    pub fn local_time(&self, time: NaiveDateTime) -> DateTime<FixedOffset> {
+        self.utc_offset().from_local_datetime(&time).unwrap()
+    }
+
+    /// `now` in the forecast's time zone, comparable to its times
+    pub fn local_now(&self, now: DateTime<Utc>) -> NaiveDateTime {
+        now.with_timezone(&self.utc_offset()).naive_local()
+    }
+
+    pub fn has_active_alert(&self, now: DateTime<Utc>) -> bool {
+        self.alerts.iter().any(|alert| alert.is_active(now))
+    }
+
+    /// Whether any precipitation is forecast between `now` and an hour later
+    pub fn precipitation_expected(&self, now: DateTime<Utc>) -> bool {
+        let now = self.local_now(now);
+        self.hourly
+            .iter()
+            .filter(|point| now < point.time + Duration::hours(1))
+            .take_while(|point| point.time < now + Duration::hours(1))
+            .any(|point| {
+                point
+                    .precipitation
+                    .map_or(point.condition.precipitation().is_some(), |precipitation| {
+                        precipitation.value > 0.0
+                    })
+            })
+    }
 }
 
-// Funktion zum Deserialisieren einer Liste von NaiveDate-Werten
-fn deserialize_date_vec<'de, D>(deserializer: D) -> core::result::Result<Vec<NaiveDate>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s: Vec<String> = Vec::deserialize(deserializer)?;
-    s.into_iter().map(parse_date::<'de, D>).collect()
+impl From<&HourlyPoint> for Observation {
+    fn from(value: &HourlyPoint) -> Self {
+        Observation {
+            time: value.time,
+            condition: value.condition,
+            daylight: value.daylight,
+            temperature: value.temperature,
+            precipitation: value.precipitation,
+            wind_speed: value.wind_speed,
+            wind_direction: value.wind_direction,
+            wind_gusts: value.wind_gusts,
+        }
+    }
 }
 
-// Funktion zum Deserialisieren einer Liste von NaiveDateTime-Werten
-fn deserialize_datetime_vec<'de, D>(
-    deserializer: D,
-) -> core::result::Result<Vec<NaiveDateTime>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s: Vec<String> = Vec::deserialize(deserializer)?;
-    s.into_iter().map(parse_datetime::<'de, D>).collect()
+impl From<ConditionCode> for WeatherCondition {
+    fn from(value: ConditionCode) -> Self {
+        match value {
+            ConditionCode::Wmo(code) => code.into(),
+            ConditionCode::Name(name) => WeatherCondition::from_name(&name),
+        }
+    }
 }
 
-fn parse_date<'de, D>(s: String) -> core::result::Result<NaiveDate, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    NaiveDate::parse_from_str(&s, "%Y-%m-%d").map_err(serde::de::Error::custom)
+impl From<WeatherCondition> for ConditionCode {
+    fn from(value: WeatherCondition) -> Self {
+        ConditionCode::Name(format!("{value:?}"))
+    }
 }
 
-fn parse_datetime<'de, D>(s: String) -> core::result::Result<NaiveDateTime, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M").map_err(serde::de::Error::custom)
+impl From<u16> for WeatherCondition {
+    /// Map a WMO weather interpretation code
+    fn from(value: u16) -> Self {
+        use Intensity::*;
+        use WeatherCondition::*;
+        match value {
+            0 => ClearSky,
+            1 => MainlyClear,
+            2 => PartlyCloudy,
+            3 => Overcast,
+            45 => Fog,
+            48 => RimeFog,
+            51 => Drizzle(Light),
+            53 => Drizzle(Moderate),
+            55 => Drizzle(Heavy),
+            56 => FreezingDrizzle(Light),
+            57 => FreezingDrizzle(Heavy),
+            61 => Rain(Light),
+            63 => Rain(Moderate),
+            65 => Rain(Heavy),
+            66 => FreezingRain(Light),
+            67 => FreezingRain(Heavy),
+            71 => Snow(Light),
+            73 => Snow(Moderate),
+            75 => Snow(Heavy),
+            77 => SnowGrains,
+            80 => RainShowers(Light),
+            81 => RainShowers(Moderate),
+            82 => RainShowers(Heavy),
+            85 => SnowShowers(Light),
+            86 => SnowShowers(Heavy),
+            95 => Thunderstorm,
+            96 => ThunderstormWithHail(Light),
+            99 => ThunderstormWithHail(Heavy),
+            _ => Unknown,
+        }
+    }
 }
 
-impl CurrentWeather {
-    /// Get a description string for Open Meteo weather code
-    pub fn description(&self) -> Cow<'_, str> {
-        match self.weathercode {
-            0 => t!("weather.clear_sky"),
-            1 => t!("weather.mainly_clear"),
-            2 => t!("weather.partly_cloudy"),
-            3 => t!("weather.overcast"),
-            45 => t!("weather.fog"),
-            48 => t!("weather.rime_fog"),
-            51 => t!("weather.light_drizzle"),
-            53 => t!("weather.moderate_drizzle"),
-            55 => t!("weather.dense_drizzle"),
-            56 => t!("weather.light_freezing_drizzle"),
-            57 => t!("weather.dense_freezing_drizzle"),
-            61 => t!("weather.light_rain"),
-            63 => t!("weather.moderate_rain"),
-            65 => t!("weather.heavy_rain"),
-            66 => t!("weather.light_freezing_rain"),
-            67 => t!("weather.heavy_freezing_rain"),
-            71 => t!("weather.light_snow"),
-            73 => t!("weather.moderate_snow"),
-            75 => t!("weather.heavy_snow"),
-            77 => t!("weather.snow_grains"),
-            80 => t!("weather.light_rain_showers"),
-            81 => t!("weather.moderate_rain_showers"),
-            82 => t!("weather.heavy_rain_showers"),
-            85 => t!("weather.light_snow_showers"),
-            86 => t!("weather.heavy_snow_showers"),
-            95 => t!("weather.thunderstorm"),
-            96 => t!("weather.thurderstorm_with_light_hail"),
-            99 => t!("weather.thunderstorm_with_heavy_hail"),
-            _ => t!("weather.unknown"),
+impl WeatherCondition {
+    /// Parse a variant name like `Rain(Light)` as written by [Serialize]
+    fn from_name(name: &str) -> Self {
+        use WeatherCondition::*;
+        let (variant, intensity) = match name.split_once('(') {
+            Some((variant, intensity)) => (variant, intensity.trim_end_matches(')')),
+            None => (name, ""),
+        };
+        let intensity = match intensity {
+            "Light" => Intensity::Light,
+            "Heavy" => Intensity::Heavy,
+            _ => Intensity::Moderate,
+        };
+        match variant {
+            "ClearSky" => ClearSky,
+            "MainlyClear" => MainlyClear,
+            "PartlyCloudy" => PartlyCloudy,
+            "Overcast" => Overcast,
+            "Fog" => Fog,
+            "RimeFog" => RimeFog,
+            "Drizzle" => Drizzle(intensity),
+            "FreezingDrizzle" => FreezingDrizzle(intensity),
+            "Rain" => Rain(intensity),
+            "FreezingRain" => FreezingRain(intensity),
+            "Sleet" => Sleet(intensity),
+            "Snow" => Snow(intensity),
+            "SnowGrains" => SnowGrains,
+            "RainShowers" => RainShowers(intensity),
+            "SnowShowers" => SnowShowers(intensity),
+            "Thunderstorm" => Thunderstorm,
+            "ThunderstormWithHail" => ThunderstormWithHail(intensity),
+            "RainAndThunder" => RainAndThunder(intensity),
+            "SleetAndThunder" => SleetAndThunder(intensity),
+            "SnowAndThunder" => SnowAndThunder(intensity),
+            _ => Unknown,
         }
     }
 
-    pub fn icon_name(&self) -> &str {
-        match self.weathercode {
-            0 => "clearsky_day",
-            1 => "fair_day",
-            2 => "partlycloudy_day",
-            3 => "cloudy",
-            45 => "fog",
-            48 => "fog",
-            51 => "lightrain",
-            53 => "lightrain",
-            55 => "lightrain",
-            56 => "lightrain",
-            57 => "lightrain",
-            61 => "lightrain",
-            63 => "rain",
-            65 => "heavyrain",
-            66 => "lightrain",
-            67 => "heavyrain",
-            71 => "lightsnow",
-            73 => "snow",
-            75 => "heavysnow",
-            77 => "lightsnow",
-            80 => "lightrain",
-            81 => "rain",
-            82 => "heavyrain",
-            85 => "lightsleet",
-            86 => "heavysleet",
-            95 => "heavyrainandthunder",
-            96 => "sleetandthunder",
-            99 => "heavysleetandthunder",
-            _ => "exclamation-circle",
+    /// Intensity of the precipitation, if the condition has one
+    pub fn intensity(&self) -> Option<Intensity> {
+        use WeatherCondition::*;
+        match *self {
+            Drizzle(intensity)
+            | FreezingDrizzle(intensity)
+            | Rain(intensity)
+            | FreezingRain(intensity)
+            | Sleet(intensity)
+            | Snow(intensity)
+            | RainShowers(intensity)
+            | SnowShowers(intensity)
+            | ThunderstormWithHail(intensity)
+            | RainAndThunder(intensity)
+            | SleetAndThunder(intensity)
+            | SnowAndThunder(intensity) => Some(intensity),
+            _ => None,
         }
     }
-}
 
-/// Search a location name on Open Meteo
-pub(crate) async fn search_location(name: &str, lang: &str) -> Result<Vec<Location>> {
-    let params = [
-        ("name", name),
-        ("language", lang),
-        ("count", "10"),
-        ("format", "json"),
-    ];
-    let url = Url::parse_with_params("https://geocoding-api.open-meteo.com/v1/search", &params)
-        .map_err(|e| Error::other(e))?;
-    let response = reqwest::get(url).await?.json::<Results>().await?;
-    Ok(response.results)
-}
+    /// Kind of precipitation falling in this condition
+    pub fn precipitation(&self) -> Option<PrecipitationKind> {
+        use WeatherCondition::*;
+        match self {
+            Drizzle(_) => Some(PrecipitationKind::Drizzle),
+            FreezingDrizzle(_) | FreezingRain(_) => Some(PrecipitationKind::FreezingRain),
+            Rain(_) | RainShowers(_) | Thunderstorm | RainAndThunder(_) => {
+                Some(PrecipitationKind::Rain)
+            }
+            Sleet(_) | SleetAndThunder(_) => Some(PrecipitationKind::Sleet),
+            Snow(_) | SnowGrains | SnowShowers(_) | SnowAndThunder(_) => {
+                Some(PrecipitationKind::Snow)
+            }
+            ThunderstormWithHail(_) => Some(PrecipitationKind::Hail),
+            _ => None,
+        }
+    }
 
-/// Get current weather on Open Meteo for specific [Location]
-pub async fn get_current_weather(location: &Location) -> Result<CurrentWeather> {
-    debug!("get_weather({location:?})");
-    let params = [
-        ("latitude", location.latitude.to_string()),
-        ("longitude", location.longitude.to_string()),
-        ("current_weather", "true".into()),
-    ];
-    let url = Url::parse_with_params("https://api.open-meteo.com/v1/forecast", &params)
-        .map_err(|e| Error::other(e))?;
-    let response = reqwest::get(url).await?.json::<WeatherResponse>().await?;
-    if let Some(error) = response.error {
-        return Err(Error::other(error));
+    /// Whether the condition includes thunder
+    pub fn has_thunder(&self) -> bool {
+        use WeatherCondition::*;
+        matches!(
+            self,
+            Thunderstorm
+                | ThunderstormWithHail(_)
+                | RainAndThunder(_)
+                | SleetAndThunder(_)
+                | SnowAndThunder(_)
+        )
     }
-    match response.current_weather {
-        Some(current_weather) => Ok(current_weather),
-        None => Err(Error::other("No current_weather received.")),
+
+    /// Get a localized description string
+    pub fn description(&self) -> Cow<'_, str> {
+        use Intensity::*;
+        use WeatherCondition::*;
+        match self {
+            ClearSky => t!("weather.clear_sky"),
+            MainlyClear => t!("weather.mainly_clear"),
+            PartlyCloudy => t!("weather.partly_cloudy"),
+            Overcast => t!("weather.overcast"),
+            Fog => t!("weather.fog"),
+            RimeFog => t!("weather.rime_fog"),
+            Drizzle(Light) => t!("weather.light_drizzle"),
+            Drizzle(Moderate) => t!("weather.moderate_drizzle"),
+            Drizzle(Heavy) => t!("weather.dense_drizzle"),
+            FreezingDrizzle(Light) => t!("weather.light_freezing_drizzle"),
+            FreezingDrizzle(_) => t!("weather.dense_freezing_drizzle"),
+            Rain(Light) => t!("weather.light_rain"),
+            Rain(Moderate) => t!("weather.moderate_rain"),
+            Rain(Heavy) => t!("weather.heavy_rain"),
+            FreezingRain(Light) => t!("weather.light_freezing_rain"),
+            FreezingRain(_) => t!("weather.heavy_freezing_rain"),
+            Sleet(Light) => t!("weather.light_sleet"),
+            Sleet(Moderate) => t!("weather.sleet"),
+            Sleet(Heavy) => t!("weather.heavy_sleet"),
+            Snow(Light) => t!("weather.light_snow"),
+            Snow(Moderate) => t!("weather.moderate_snow"),
+            Snow(Heavy) => t!("weather.heavy_snow"),
+            SnowGrains => t!("weather.snow_grains"),
+            RainShowers(Light) => t!("weather.light_rain_showers"),
+            RainShowers(Moderate) => t!("weather.moderate_rain_showers"),
+            RainShowers(Heavy) => t!("weather.heavy_rain_showers"),
+            SnowShowers(Light) => t!("weather.light_snow_showers"),
+            SnowShowers(_) => t!("weather.heavy_snow_showers"),
+            Thunderstorm => t!("weather.thunderstorm"),
+            ThunderstormWithHail(Heavy) => t!("weather.thunderstorm_with_heavy_hail"),
+            ThunderstormWithHail(_) => t!("weather.thurderstorm_with_light_hail"),
+            RainAndThunder(Light) => t!("weather.light_rain_and_thunder"),
+            RainAndThunder(Moderate) => t!("weather.rain_and_thunder"),
+            RainAndThunder(Heavy) => t!("weather.heavy_rain_and_thunder"),
+            SleetAndThunder(Light) => t!("weather.light_sleet_and_thunder"),
+            SleetAndThunder(Moderate) => t!("weather.sleet_and_thunder"),
+            SleetAndThunder(Heavy) => t!("weather.heavy_sleet_and_thunder"),
+            SnowAndThunder(Light) => t!("weather.light_snow_and_thunder"),
+            SnowAndThunder(Moderate) => t!("weather.snow_and_thunder"),
+            SnowAndThunder(Heavy) => t!("weather.heavy_snow_and_thunder"),
+            Unknown => t!("weather.unknown"),
+        }
     }
-}
 
-/// Get forecast weather on Open Meteo for specific [Location]
-pub async fn get_forecast(location: &Location) -> Result<WeatherResponse> {
-    debug!("get_forecast({location:?})");
-    let params = [
-        ("latitude", location.latitude.to_string()),
-        ("longitude", location.longitude.to_string()),
-        ("current", "temperature_2m,precipitation,weather_code,wind_speed_10m,wind_direction_10m,wind_gusts_10m".into()),
-        ("hourly", "temperature_2m,precipitation,weather_code,wind_speed_10m,wind_direction_10m,wind_gusts_10m".into()),
-        ("daily", "weather_code,temperature_2m_max,temperature_2m_min,precipitation_sum,precipitation_hours,precipitation_probability_max,wind_speed_10m_max,wind_gusts_10m_max,wind_direction_10m_dominant".into()),
-        // ("timezone", "Europe%2FBerlin".into()),
-        ("forecast_days", "7".into()),
-        ("forecast_hours", "12".into()),
-    ];
-    let url = Url::parse_with_params("https://api.open-meteo.com/v1/forecast", &params)
-        .map_err(|e| Error::other(e))?;
-    let response = reqwest::get(url).await?.json::<WeatherResponse>().await?;
-    if let Some(error) = response.error {
-        return Err(Error::other(error));
+    /// Get the file name of the matching icon in an icon theme
+    pub fn icon_name(&self, daylight: Daylight) -> String {
+        use WeatherCondition::*;
+        match self {
+            ClearSky | MainlyClear | PartlyCloudy => {
+                format!("{}{}", self.base_icon_name(), daylight.icon_suffix())
+            }
+            _ => self.base_icon_name().into(),
+        }
+    }
+
+    /// Icon name without day or night variant
+    pub fn base_icon_name(&self) -> &str {
+        use Intensity::*;
+        use WeatherCondition::*;
+        match self {
+            ClearSky => "clearsky",
+            MainlyClear => "fair",
+            PartlyCloudy => "partlycloudy",
+            Overcast => "cloudy",
+            Fog | RimeFog => "fog",
+            Drizzle(_) | FreezingDrizzle(_) => "lightrain",
+            Rain(_) | FreezingRain(_) | RainShowers(_) => match self.intensity() {
+                Some(Light) => "lightrain",
+                Some(Heavy) => "heavyrain",
+                _ => "rain",
+            },
+            Sleet(Light) => "lightsleet",
+            Sleet(Moderate) => "sleet",
+            Sleet(Heavy) => "heavysleet",
+            Snow(Light) | SnowGrains => "lightsnow",
+            Snow(Moderate) => "snow",
+            Snow(Heavy) => "heavysnow",
+            SnowShowers(Heavy) => "heavysleet",
+            SnowShowers(_) => "lightsleet",
+            Thunderstorm | RainAndThunder(_) => "heavyrainandthunder",
+            ThunderstormWithHail(_) | SleetAndThunder(_) | SnowAndThunder(_) => {
+                match self.intensity() {
+                    Some(Heavy) => "heavysleetandthunder",
+                    _ => "sleetandthunder",
+                }
+            }
+            Unknown => "exclamation-circle",
+        }
     }
-    Ok(response)
 }
 
 #[derive(Embed)]
 #[folder = "assets"]
-#[include = "*.ico"]
+#[include = "*.svg"]
 pub struct EmbeddedFiles;
 
-/// Load [Icon] from embeded file
+/// Logical size of tray icons where the system does not tell it
+#[cfg(not(windows))]
+const TRAY_ICON_SIZE: u32 = if cfg!(target_os = "macos") { 18 } else { 22 };
+
+/// Pixel size of tray icons, including the display scale factor
+pub fn tray_icon_size() -> u32 {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_CXSMICON};
+        // Already scaled to the system DPI
+        match unsafe { GetSystemMetrics(SM_CXSMICON) } {
+            size if size > 0 => size as u32,
+            _ => 16,
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        // The menu bar of macOS is always drawn at Retina resolution if available
+        let scale_factor = if cfg!(target_os = "macos") {
+            2
+        } else {
+            std::env::var("GDK_SCALE")
+                .ok()
+                .and_then(|scale| scale.parse::<u32>().ok())
+                .unwrap_or(1)
+                .max(1)
+        };
+        TRAY_ICON_SIZE * scale_factor
+    }
+}
+
+/// Weather icons rasterised by `build.rs`, as ICOs with a PNG entry per size, by paths like
+/// `weathericons/metno/ico/clearsky_day.ico`
+static RASTERIZED_ICONS: &[(&str, &[u8])] =
+    include!(concat!(env!("OUT_DIR"), "/rasterized_icons.rs"));
+
+/// Embedded icon shown for errors and weather without an icon
+pub const ERROR_ICON: &str = "tabler-icons/exclamation-circle.svg";
+
+/// Load [Icon] from an embedded icon file, at [tray_icon_size]
 pub fn get_icon(path: &str) -> Result<Icon> {
+    icon_from_rgba(get_icon_image(path, tray_icon_size())?)
+}
+
+/// Render embeded SVG file into a square bitmap of `size` pixels
+///
+/// Weather icons are taken from [RASTERIZED_ICONS] where they have an entry of `size` pixels.
+pub fn get_icon_image(path: &str, size: u32) -> Result<RgbaImage> {
+    if let Some(image) = rasterized_icon(path, size) {
+        return Ok(image);
+    }
     let bytes = EmbeddedFiles::get(path)
         .ok_or_else(|| Error::other(format!("Icon file {path} not found.")))?;
-    let img =
-        load_from_memory_with_format(&bytes.data, image::ImageFormat::Ico).map_err(Error::other)?;
-    let rgba = img.to_rgba8();
-    let raw = rgba.into_raw();
-    let icon = Icon::from_rgba(raw, img.width(), img.height()).map_err(Error::other)?;
+    render_svg_image(&bytes.data, size)
+}
+
+/// Rasterised entry of `size` pixels for the embedded SVG file at `path`
+fn rasterized_icon(path: &str, size: u32) -> Option<RgbaImage> {
+    let ico_path = format!(
+        "{}.ico",
+        path.strip_suffix(".svg")?.replacen("/svg/", "/ico/", 1)
+    );
+    let (_, ico) = RASTERIZED_ICONS
+        .iter()
+        .find(|(path, _)| *path == ico_path)?;
+    let png = ico_entry(ico, size)?;
+    let image = image::load_from_memory_with_format(png, ImageFormat::Png).ok()?;
+    Some(image.to_rgba8())
+}
+
+/// Data of the entry with `size` pixels in an ICO file
+fn ico_entry(ico: &[u8], size: u32) -> Option<&[u8]> {
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap()) as usize;
+    let count = u16::from_le_bytes(ico.get(4..6)?.try_into().unwrap());
+    (0..count as usize).find_map(|index| {
+        let entry = ico.get(6 + index * 16..6 + (index + 1) * 16)?;
+        // A width of 0 stands for 256 pixels
+        let width = if entry[0] == 0 { 256 } else { entry[0] as u32 };
+        if width != size {
+            return None;
+        }
+        let (length, offset) = (read_u32(&entry[8..12]), read_u32(&entry[12..16]));
+        ico.get(offset..offset + length)
+    })
+}
+
+/// Scale a raster image to a square of `size` pixels
+pub fn fit_image(image: RgbaImage, size: u32) -> RgbaImage {
+    if image.dimensions() == (size, size) {
+        return image;
+    }
+    imageops::resize(&image, size, size, imageops::FilterType::Lanczos3)
+}
+
+/// Render an SVG image into a square bitmap of `size` pixels
+pub fn render_svg_image(data: &[u8], size: u32) -> Result<RgbaImage> {
+    let tree = usvg::Tree::from_data(data, &usvg::Options::default()).map_err(Error::other)?;
+    let mut pixmap = tiny_skia::Pixmap::new(size, size)
+        .ok_or_else(|| Error::other(format!("Invalid icon size {size}.")))?;
+    let scale = size as f32 / tree.size().width().max(tree.size().height());
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    let rgba = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    RgbaImage::from_raw(size, size, rgba).ok_or_else(|| Error::other("Invalid SVG rendering."))
+}
+
+/// Convert a decoded image into an [Icon]
+pub fn icon_from_rgba(rgba: RgbaImage) -> Result<Icon> {
+    let (width, height) = rgba.dimensions();
+    let icon = Icon::from_rgba(rgba.into_raw(), width, height).map_err(Error::other)?;
     Ok(icon)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::weather::WeatherResponse;
+    use chrono::{DateTime, Duration, NaiveDateTime};
+
+    use crate::units::{
+        Precipitation, PrecipitationUnit, Speed, SpeedUnit, Temperature, TemperatureUnit,
+    };
+
+    use super::{
+        get_icon, get_icon_image, rasterized_icon, Alert, Daylight, EmbeddedFiles, Forecast,
+        HourlyPoint, Intensity, PrecipitationKind, WeatherCondition,
+    };
+
+    #[test]
+    fn get_weather() {}
 
     #[test]
-    fn decode_weatherresponse() {
-        let result: Result<WeatherResponse, serde_json::Error> =
-            serde_json::from_str(include_str!("../tests/data/weather_response_forecast.json"));
-        assert!(matches!(result, Ok(_)));
+    fn forecast_badges() {
+        let time = NaiveDateTime::parse_from_str("2024-10-21T17:00", "%Y-%m-%dT%H:%M").unwrap();
+        let point =
+            |hour: i64, condition: WeatherCondition, precipitation: Option<f32>| HourlyPoint {
+                time: time + Duration::hours(hour),
+                condition,
+                daylight: Daylight::Day,
+                temperature: Temperature::new(12.0, TemperatureUnit::Celsius),
+                precipitation: precipitation
+                    .map(|value| Precipitation::new(value, PrecipitationUnit::Millimetres)),
+                wind_speed: Speed::new(5.0, SpeedUnit::KilometresPerHour),
+                wind_direction: 180,
+                wind_gusts: None,
+            };
+        let mut forecast = Forecast {
+            hourly: vec![
+                point(0, WeatherCondition::Overcast, Some(0.0)),
+                point(1, WeatherCondition::Rain(Intensity::Light), None),
+                point(2, WeatherCondition::Overcast, Some(1.2)),
+            ],
+            ..Default::default()
+        };
+        let now = DateTime::parse_from_rfc3339("2024-10-21T17:20:00Z")
+            .unwrap()
+            .to_utc();
+        assert!(forecast.precipitation_expected(now));
+        assert!(!forecast.precipitation_expected(now - Duration::hours(1)));
+        assert!(forecast.precipitation_expected(now + Duration::hours(1)));
+
+        assert!(!forecast.has_active_alert(now));
+        forecast.alerts.push(Alert {
+            event: "Fog".into(),
+            onset: Some(now - Duration::hours(1)),
+            ends: Some(now),
+        });
+        assert!(!forecast.has_active_alert(now));
+        assert!(forecast.has_active_alert(now - Duration::minutes(1)));
     }
 
     #[test]
-    fn get_weather() {}
+    fn render_embedded_icons() {
+        for path in EmbeddedFiles::iter() {
+            assert!(get_icon(&path).is_ok(), "{path}");
+        }
+    }
+
+    #[test]
+    fn rasterize_weather_icons() {
+        let weather_icons = EmbeddedFiles::iter().filter(|path| path.starts_with("weathericons/"));
+        for path in weather_icons {
+            let image = rasterized_icon(&path, 22).unwrap();
+            assert_eq!(image.dimensions(), (22, 22), "{path}");
+            assert!(image.pixels().any(|pixel| pixel[3] > 0), "{path}");
+            assert!(rasterized_icon(&path, 23).is_none());
+            assert_eq!(get_icon_image(&path, 23).unwrap().dimensions(), (23, 23));
+        }
+        assert!(rasterized_icon("tabler-icons/exclamation-circle.svg", 22).is_none());
+    }
+
+    #[test]
+    fn map_wmo_codes() {
+        assert_eq!(WeatherCondition::from(0), WeatherCondition::ClearSky);
+        assert_eq!(
+            WeatherCondition::from(65),
+            WeatherCondition::Rain(Intensity::Heavy)
+        );
+        assert_eq!(WeatherCondition::from(42), WeatherCondition::Unknown);
+
+        let condition = WeatherCondition::from(96);
+        assert!(condition.has_thunder());
+        assert_eq!(condition.intensity(), Some(Intensity::Light));
+        assert_eq!(condition.precipitation(), Some(PrecipitationKind::Hail));
+    }
+
+    #[test]
+    fn decode_wmo_code() {
+        let conditions: Vec<WeatherCondition> = serde_json::from_str("[3, 61]").unwrap();
+        assert_eq!(
+            conditions,
+            vec![
+                WeatherCondition::Overcast,
+                WeatherCondition::Rain(Intensity::Light)
+            ]
+        );
+    }
+
+    #[test]
+    fn serialize_condition() {
+        let conditions = vec![
+            WeatherCondition::ClearSky,
+            WeatherCondition::Rain(Intensity::Light),
+            WeatherCondition::SnowAndThunder(Intensity::Heavy),
+            WeatherCondition::Unknown,
+        ];
+        let json = serde_json::to_string(&conditions).unwrap();
+        assert_eq!(
+            json,
+            r#"["ClearSky","Rain(Light)","SnowAndThunder(Heavy)","Unknown"]"#
+        );
+        let decoded: Vec<WeatherCondition> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, conditions);
+    }
 }